@@ -11,7 +11,8 @@ impl AABB {
         AABB { minimum, maximum }
     }
 
-    pub fn hit(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+    /// Finds the part of the ray between `t_min` and `t_max` that's inside the box, if any.
+    pub fn intersect(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
         for a in 0..=2 {
             let inv_d = 1.0 / ray.direction[a];
             let mut t0 = (self.minimum[a] - ray.origin[a]) * inv_d;
@@ -22,10 +23,36 @@ impl AABB {
             t_min = if t0 > t_min { t0 } else { t_min };
             t_max = if t1 < t_max { t1 } else { t_max };
             if t_max <= t_min {
-                return false;
+                return None;
             }
         }
-        true
+        Some((t_min, t_max))
+    }
+
+    /// Grows any axis thinner than `delta` to that thickness, since a box with no extent
+    /// along an axis can never be hit.
+    pub fn padded(&self, delta: f64) -> AABB {
+        let mut minimum = [self.minimum.x, self.minimum.y, self.minimum.z];
+        let mut maximum = [self.maximum.x, self.maximum.y, self.maximum.z];
+        for a in 0..=2 {
+            if maximum[a] - minimum[a] < delta {
+                minimum[a] -= delta / 2.0;
+                maximum[a] += delta / 2.0;
+            }
+        }
+        AABB::new(
+            Vec3::new(minimum[0], minimum[1], minimum[2]),
+            Vec3::new(maximum[0], maximum[1], maximum[2]),
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.maximum - self.minimum;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * &(self.minimum + self.maximum)
     }
 
     pub fn bounding_box(box0: &AABB, box1: &AABB) -> AABB {
@@ -0,0 +1,24 @@
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::scene::unsupported;
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+/// What a ray sees when it leaves the scene without hitting anything.
+pub trait Background {
+    fn color(&self, ray: &Ray) -> Color;
+    /// Picks a direction favouring the brightest parts of the background, for backgrounds
+    /// that are worth aiming at. Whether this returns `None` mustn't depend on `rng`.
+    fn sample_direction(&self, _rng: &mut dyn RngCore) -> Option<Vec3> {
+        None
+    }
+    /// The density, per unit solid angle, of `sample_direction` picking `direction`.
+    fn pdf(&self, _direction: &Vec3) -> f64 {
+        0.0
+    }
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Err(unsupported("background"))
+    }
+}
@@ -0,0 +1,30 @@
+use crate::background::Background;
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::scene::{color, hash, string};
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+/// The same color in every direction, e.g. black for scenes lit only by their own lights.
+pub struct Constant {
+    color: Color,
+}
+
+impl Constant {
+    pub fn new(color: Color) -> Constant {
+        Constant { color }
+    }
+}
+
+impl Background for Constant {
+    fn color(&self, _ray: &Ray) -> Color {
+        self.color
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("constant")),
+            ("color", color(&self.color)),
+        ]))
+    }
+}
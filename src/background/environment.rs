@@ -0,0 +1,251 @@
+use crate::background::Background;
+use crate::color::Color;
+use crate::image::HDR;
+use crate::ray::Ray;
+use crate::scene::{hash, real, string, unsupported};
+use crate::vec3::Vec3;
+use rand::{Rng, RngCore};
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{Error as IoError, Result as IoResult};
+use std::path::Path;
+use yaml_rust::Yaml;
+
+/// An equirectangular environment map: the image's x axis wraps once around the vertical, with
+/// its middle straight ahead down -z, and its y axis runs from straight up to straight down.
+pub struct Environment {
+    image: HDR,
+    file: Option<String>,
+    rotation: f64,
+    intensity: f64,
+    /// How likely each row is to be sampled, as a cumulative distribution
+    row_cdf: Vec<f64>,
+    /// How likely each pixel is to be sampled given its row, cumulatively along each row
+    column_cdfs: Vec<f64>,
+    /// How likely each pixel is to be sampled
+    pixel_probabilities: Vec<f64>,
+}
+
+impl Environment {
+    /// Wraps `image` around the scene, turned `rotation` degrees about the vertical axis and
+    /// scaled in brightness by `intensity`.
+    pub fn new(image: HDR, rotation: f64, intensity: f64) -> Environment {
+        let width = image.image_width as usize;
+        let height = image.image_height as usize;
+
+        // Pixels are sampled in proportion to their brightness and to the solid angle they
+        // cover, which shrinks towards the poles.
+        let mut pixel_probabilities: Vec<f64> = image
+            .image_data
+            .iter()
+            .enumerate()
+            .map(|(idx, color)| {
+                let theta = ((idx / width) as f64 + 0.5) / height as f64 * PI;
+                luminance(color).max(0.0) * theta.sin()
+            })
+            .collect();
+        let total: f64 = pixel_probabilities.iter().sum();
+        if total > 0.0 {
+            for probability in &mut pixel_probabilities {
+                *probability /= total;
+            }
+        }
+
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut column_cdfs = Vec::with_capacity(width * height);
+        let mut cumulative = 0.0;
+        for row in pixel_probabilities.chunks(width) {
+            let row_total: f64 = row.iter().sum();
+            let mut row_cumulative = 0.0;
+            for probability in row {
+                row_cumulative += probability;
+                column_cdfs.push(if row_total > 0.0 {
+                    row_cumulative / row_total
+                } else {
+                    0.0
+                });
+            }
+            cumulative += row_total;
+            row_cdf.push(cumulative);
+        }
+
+        Environment {
+            image,
+            file: None,
+            rotation,
+            intensity,
+            row_cdf,
+            column_cdfs,
+            pixel_probabilities,
+        }
+    }
+
+    /// Loads an environment map from a Radiance `.hdr` file. See `new`.
+    pub fn load(path: &Path, rotation: f64, intensity: f64) -> IoResult<Environment> {
+        let in_file =
+            |err: IoError| IoError::new(err.kind(), format!("{}: {}", path.display(), err));
+        let image = HDR::decode(&fs::read(path).map_err(in_file)?).map_err(in_file)?;
+        let mut environment = Environment::new(image, rotation, intensity);
+        // Keep hold of where it came from so the scene can be written back out, wherever
+        // that ends up.
+        let path = fs::canonicalize(path).map_err(in_file)?;
+        environment.file = Some(path.to_string_lossy().to_string());
+        Ok(environment)
+    }
+
+    /// Turns a direction from the scene into the image's frame of reference.
+    fn to_image(&self, direction: &Vec3) -> Vec3 {
+        rotate_y(&Vec3::unit_vector(direction), -self.rotation)
+    }
+
+    /// Finds the image coordinates, each in [0, 1], of a direction in the image's frame.
+    fn coordinates(&self, direction: &Vec3) -> (f64, f64) {
+        let u = direction.x.atan2(-direction.z) / (2.0 * PI) + 0.5;
+        let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    fn pixel_index(&self, u: f64, v: f64) -> usize {
+        let width = self.image.image_width as usize;
+        let height = self.image.image_height as usize;
+        let x = ((u * width as f64) as usize).min(width - 1);
+        let y = ((v * height as f64) as usize).min(height - 1);
+        y * width + x
+    }
+}
+
+impl Background for Environment {
+    fn color(&self, ray: &Ray) -> Color {
+        let (u, v) = self.coordinates(&self.to_image(&ray.direction));
+        self.intensity * &self.image.image_data[self.pixel_index(u, v)]
+    }
+
+    fn sample_direction(&self, rng: &mut dyn RngCore) -> Option<Vec3> {
+        // The total can be a hair away from 1 after rounding
+        let total = match self.row_cdf.last() {
+            Some(total) if *total > 0.0 => *total,
+            _ => return None,
+        };
+        let width = self.image.image_width as usize;
+        let height = self.image.image_height as usize;
+
+        let target = rng.gen_range(0.0..total);
+        let y = self
+            .row_cdf
+            .partition_point(|cumulative| *cumulative <= target)
+            .min(height - 1);
+        let target = rng.gen_range(0.0..1.0);
+        let x = self.column_cdfs[y * width..(y + 1) * width]
+            .partition_point(|cumulative| *cumulative <= target)
+            .min(width - 1);
+
+        let u = (x as f64 + rng.gen_range(0.0..1.0)) / width as f64;
+        let v = (y as f64 + rng.gen_range(0.0..1.0)) / height as f64;
+        let phi = (u - 0.5) * 2.0 * PI;
+        let theta = v * PI;
+        let direction = Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+        Some(rotate_y(&direction, self.rotation))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let direction = self.to_image(direction);
+        let sin_theta = (1.0 - direction.y * direction.y).max(0.0).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (u, v) = self.coordinates(&direction);
+        let pixels = self.pixel_probabilities.len() as f64;
+        // Convert from the density over the image to the density over the sphere
+        self.pixel_probabilities[self.pixel_index(u, v)] * pixels / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        let file = self
+            .file
+            .as_ref()
+            .ok_or_else(|| unsupported("environment map not loaded from a file"))?;
+        Ok(hash(vec![
+            ("type", string("environment")),
+            ("file", string(file)),
+            ("rotation", real(self.rotation)),
+            ("intensity", real(self.intensity)),
+        ]))
+    }
+}
+
+fn luminance(color: &Color) -> f64 {
+    0.2126 * color.red + 0.7152 * color.green + 0.0722 * color.blue
+}
+
+/// Rotates `v` counterclockwise (seen from above) about the y axis.
+fn rotate_y(v: &Vec3, degrees: f64) -> Vec3 {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Vec3::new(cos * v.x + sin * v.z, v.y, cos * v.z - sin * v.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// A dim sky with a small, bright sun just to the right of straight ahead.
+    fn sky(rotation: f64) -> Environment {
+        let (width, height) = (16, 8);
+        let mut image_data = vec![Color::new(0.1, 0.1, 0.2); width * height];
+        image_data[3 * width + 9] = Color::new(1000.0, 900.0, 800.0);
+        let image = HDR {
+            image_width: width as u32,
+            image_height: height as u32,
+            image_data,
+        };
+        Environment::new(image, rotation, 2.0)
+    }
+
+    #[test]
+    fn test_color() {
+        let environment = sky(0.0);
+        let sun = Vec3::new(0.65, 0.2, -1.0);
+        let ray = Ray::new(Vec3::origin(), sun, 0.0);
+        assert_eq!(environment.color(&ray), Color::new(2000.0, 1800.0, 1600.0));
+        let ray = Ray::new(Vec3::origin(), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert_eq!(environment.color(&ray), Color::new(0.2, 0.2, 0.4));
+
+        // Turning the map turns the sun with it
+        let rotated = sky(90.0);
+        let ray = Ray::new(Vec3::origin(), rotate_y(&sun, 90.0), 0.0);
+        assert_eq!(rotated.color(&ray), Color::new(2000.0, 1800.0, 1600.0));
+    }
+
+    #[test]
+    fn test_sampling() {
+        let environment = sky(30.0);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        // Most samples head for the sun, and their density matches how they were picked
+        let samples = 10000;
+        let mut sunny = 0;
+        for _ in 0..samples {
+            let direction = environment.sample_direction(&mut rng).unwrap();
+            let color = environment.color(&Ray::new(Vec3::origin(), direction, 0.0));
+            if color.red > 100.0 {
+                sunny += 1;
+                // Nearly all of the density, spread over one of the map's 128 pixels
+                assert!(environment.pdf(&direction) > 5.0);
+            }
+        }
+        assert!(sunny > samples * 9 / 10);
+
+        // The density integrates to one over the sphere
+        let mut total = 0.0;
+        for _ in 0..samples {
+            let direction = Vec3::random_unit_vector(&mut rng);
+            total += environment.pdf(&direction) * 4.0 * PI;
+        }
+        assert!((total / samples as f64 - 1.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::background::Background;
+use crate::color::Color;
+use crate::ray::Ray;
+use crate::scene::{color, hash, string};
+use crate::vec3::Vec3;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+/// Blends linearly from one color straight down to another straight up.
+pub struct Gradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl Gradient {
+    pub fn new(bottom: Color, top: Color) -> Gradient {
+        Gradient { bottom, top }
+    }
+
+    /// White fading to light blue, as in the book.
+    pub fn sky() -> Gradient {
+        Gradient::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for Gradient {
+    fn color(&self, ray: &Ray) -> Color {
+        let unit_direction = Vec3::unit_vector(&ray.direction);
+        let t = 0.5 * (unit_direction.y + 1.0);
+        (1.0 - t) * &self.bottom + t * &self.top
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("gradient")),
+            ("bottom", color(&self.bottom)),
+            ("top", color(&self.top)),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gradient() {
+        let gradient = Gradient::sky();
+        let up = Ray::new(Vec3::origin(), Vec3::new(0.0, 2.0, 0.0), 0.0);
+        assert_eq!(gradient.color(&up), Color::new(0.5, 0.7, 1.0));
+        let down = Ray::new(Vec3::origin(), Vec3::new(0.0, -2.0, 0.0), 0.0);
+        assert_eq!(gradient.color(&down), Color::new(1.0, 1.0, 1.0));
+    }
+}
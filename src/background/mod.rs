@@ -0,0 +1,8 @@
+mod background;
+pub use background::Background;
+mod environment;
+pub use environment::Environment;
+mod constant;
+pub use constant::Constant;
+mod gradient;
+pub use gradient::Gradient;
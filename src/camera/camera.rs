@@ -1,15 +1,25 @@
 use crate::ray::Ray;
+use crate::scene::{hash, real, vec3};
 use crate::vec3::Vec3;
+use rand::{Rng, RngCore};
+use yaml_rust::Yaml;
 
 pub struct Camera {
     pub origin: Vec3,
+    pub look_at: Vec3,
+    pub vup: Vec3,
+    pub vertical_fov: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
     pub lower_left_corner: Vec3,
     pub horizontal: Vec3,
     pub vertical: Vec3,
     pub u: Vec3,
     pub v: Vec3,
-    pub w: Vec3,
     pub lens_radius: f64,
+    /// Shutter open and close times: each ray is sent at a random time between the two
+    pub time0: f64,
+    pub time1: f64,
 }
 
 impl Camera {
@@ -27,11 +37,11 @@ impl Camera {
         let viewport_height = 2.0 * h;
         let viewport_width = aspect_ratio * viewport_height;
 
-        let w = Vec3::unit_vector(&(look_from.clone() - look_at.clone()));
-        let u = Vec3::unit_vector(&Vec3::cross(&vup, &w));
+        let w = Vec3::unit_vector(&(*look_from - *look_at));
+        let u = Vec3::unit_vector(&Vec3::cross(vup, &w));
         let v = Vec3::cross(&w, &u);
 
-        let origin = look_from.clone();
+        let origin = *look_from;
         let horizontal = focus_dist * viewport_width * &u;
         let vertical = focus_dist * viewport_height * &v;
         let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * &w;
@@ -40,24 +50,52 @@ impl Camera {
 
         Camera {
             origin,
+            look_at: *look_at,
+            vup: *vup,
+            vertical_fov,
+            aperture,
+            focus_dist,
             lower_left_corner,
             horizontal,
             vertical,
             u,
             v,
-            w,
             lens_radius,
+            time0: 0.0,
+            time1: 0.0,
         }
     }
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * &Vec3::random_in_unit_disk();
+    pub fn get_ray(&self, s: f64, t: f64, rng: &mut dyn RngCore) -> Ray {
+        let rd = self.lens_radius * &Vec3::random_in_unit_disk(rng);
         let offset = self.u * rd.x + self.v * rd.y;
+        let time = if self.time1 > self.time0 {
+            rng.gen_range(self.time0..self.time1)
+        } else {
+            self.time0
+        };
 
-        return Ray::new(
+        Ray::new(
             self.origin + offset,
-            self.lower_left_corner.clone() + s * &self.horizontal + t * &self.vertical
+            self.lower_left_corner + s * &self.horizontal + t * &self.vertical
                 - self.origin
                 - offset,
-        );
+            time,
+        )
+    }
+
+    pub fn to_yaml(&self) -> Yaml {
+        let mut entries = vec![
+            ("look_from", vec3(&self.origin)),
+            ("look_at", vec3(&self.look_at)),
+            ("vup", vec3(&self.vup)),
+            ("vertical_fov", real(self.vertical_fov)),
+            ("aperture", real(self.aperture)),
+            ("focus_dist", real(self.focus_dist)),
+        ];
+        if self.time0 != 0.0 || self.time1 != 0.0 {
+            entries.push(("time0", real(self.time0)));
+            entries.push(("time1", real(self.time1)));
+        }
+        hash(entries)
     }
 }
@@ -1,11 +1,11 @@
 use crate::vec3::Vec3;
-use rand::Rng;
+use rand::{Rng, RngCore};
 use std::ops::Add;
 use std::ops::AddAssign;
 use std::ops::Mul;
 use std::ops::MulAssign;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Color {
     pub red: f64,
     pub green: f64,
@@ -29,10 +29,20 @@ impl Color {
         (self.blue * 255.0).round() as i32
     }
 
-    pub fn random(min: f64, max: f64) -> Color {
-        let red = rand::thread_rng().gen_range(min..max);
-        let green = rand::thread_rng().gen_range(min..max);
-        let blue = rand::thread_rng().gen_range(min..max);
+    /// Applies a gamma of 2 and clamps each channel to [0, 1), ready for writing out as a low
+    /// dynamic range pixel.
+    pub fn gamma_corrected(&self) -> Color {
+        Color {
+            red: self.red.sqrt().clamp(0.0, 0.9999999),
+            green: self.green.sqrt().clamp(0.0, 0.9999999),
+            blue: self.blue.sqrt().clamp(0.0, 0.9999999),
+        }
+    }
+
+    pub fn random(rng: &mut dyn RngCore, min: f64, max: f64) -> Color {
+        let red = rng.gen_range(min..max);
+        let green = rng.gen_range(min..max);
+        let blue = rng.gen_range(min..max);
         Color::new(red, green, blue)
     }
 }
@@ -0,0 +1,184 @@
+use crate::color::Color;
+use crate::image::Image;
+use std::io::prelude::*;
+
+/// Uncompressed scanline OpenEXR image with half float RGB channels, storing linear radiance.
+pub struct EXR {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub image_data: Vec<Color>,
+}
+
+impl Image for EXR {
+    fn write(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+
+        // Channels are stored in alphabetical order
+        let mut channels = vec![];
+        for name in b"BGR" {
+            channels.extend_from_slice(&[*name, 0]);
+            // Half float, not perceptually linear, x and y sampling of 1
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&[0, 0, 0, 0]);
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&1i32.to_le_bytes());
+        }
+        channels.push(0);
+
+        let mut window = vec![];
+        for value in &[
+            0,
+            0,
+            self.image_width as i32 - 1,
+            self.image_height as i32 - 1,
+        ] {
+            window.extend_from_slice(&value.to_le_bytes());
+        }
+
+        write_attribute(&mut header, "channels", "chlist", &channels);
+        write_attribute(&mut header, "compression", "compression", &[0]);
+        write_attribute(&mut header, "dataWindow", "box2i", &window);
+        write_attribute(&mut header, "displayWindow", "box2i", &window);
+        write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        write_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        write_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        header.push(0);
+
+        // Each scanline is its own chunk: the y coordinate and data size, followed by the
+        // line's blue, green and red values in turn.
+        let line_size = self.image_width as usize * 3 * 2;
+        let chunk_size = 8 + line_size;
+        let offsets_start = header.len();
+        for y in 0..self.image_height as usize {
+            let offset = offsets_start + self.image_height as usize * 8 + y * chunk_size;
+            header.extend_from_slice(&(offset as u64).to_le_bytes());
+        }
+        out.write_all(&header)?;
+
+        let mut chunk = vec![];
+        for (y, row) in self
+            .image_data
+            .chunks(self.image_width as usize)
+            .enumerate()
+        {
+            chunk.clear();
+            chunk.extend_from_slice(&(y as i32).to_le_bytes());
+            chunk.extend_from_slice(&(line_size as i32).to_le_bytes());
+            for channel in &[|c: &Color| c.blue, |c: &Color| c.green, |c: &Color| c.red] {
+                for pixel in row {
+                    chunk.extend_from_slice(&to_half(channel(pixel) as f32).to_le_bytes());
+                }
+            }
+            out.write_all(&chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_attribute(image: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    image.extend_from_slice(name.as_bytes());
+    image.push(0);
+    image.extend_from_slice(kind.as_bytes());
+    image.push(0);
+    image.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    image.extend_from_slice(value);
+}
+
+/// Converts to an IEEE 754 half precision float, rounding to nearest even.
+fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // Infinity or NaN
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        // Too large, becomes infinity
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // Subnormal or zero
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round_up = remainder > halfway || (remainder == halfway && half & 1 == 1);
+        return sign | (half + round_up as u32) as u16;
+    }
+
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+    // Rounding up may carry into the exponent, which is exactly what we want.
+    sign | (half + round_up as u32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_to_half() {
+        assert_eq!(to_half(0.0), 0x0000);
+        assert_eq!(to_half(-0.0), 0x8000);
+        assert_eq!(to_half(1.0), 0x3c00);
+        assert_eq!(to_half(0.5), 0x3800);
+        assert_eq!(to_half(-2.0), 0xc000);
+        assert_eq!(to_half(65504.0), 0x7bff);
+        assert_eq!(to_half(1e6), 0x7c00);
+        assert_eq!(to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(to_half(2f32.powi(-14)), 0x0400);
+        assert_eq!(to_half(1e-9), 0x0000);
+        // 1 + 2^-11 is halfway between 1 and the next half, and rounds to even
+        assert_eq!(to_half(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+    }
+
+    #[test]
+    fn test_layout() {
+        let exr = EXR {
+            image_width: 2,
+            image_height: 3,
+            image_data: vec![Color::new(1.0, 0.5, 0.25); 6],
+        };
+        let mut image = vec![];
+        exr.write(&mut image).unwrap();
+        assert_eq!(&image[..4], &[0x76, 0x2f, 0x31, 0x01]);
+
+        // The header is followed by a table of chunk offsets, then each chunk has 8 bytes of
+        // y coordinate and size before its 2 pixels * 3 channels * 2 bytes of data.
+        let chunk_size = 8 + 12;
+        let table = image.len() - 3 * chunk_size - 3 * 8;
+        for y in 0..3 {
+            let offset = u64::from_le_bytes(image[table + y * 8..][..8].try_into().unwrap());
+            assert_eq!(offset as usize, table + 3 * 8 + y * chunk_size);
+        }
+        let last_chunk = &image[image.len() - 20..];
+        assert_eq!(&last_chunk[..4], &2i32.to_le_bytes());
+        assert_eq!(&last_chunk[8..10], &0x3400u16.to_le_bytes());
+        assert_eq!(&last_chunk[16..18], &0x3c00u16.to_le_bytes());
+    }
+}
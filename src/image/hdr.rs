@@ -0,0 +1,306 @@
+use crate::color::Color;
+use crate::image::Image;
+use std::io::prelude::*;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+
+/// Radiance RGBE image, storing linear radiance with a shared exponent per pixel.
+pub struct HDR {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub image_data: Vec<Color>,
+}
+
+impl Image for HDR {
+    fn write(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        write!(
+            out,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.image_height, self.image_width
+        )?;
+
+        let mut line = vec![];
+        for row in self.image_data.chunks(self.image_width as usize) {
+            line.clear();
+            let pixels: Vec<[u8; 4]> = row.iter().map(to_rgbe).collect();
+            // Run length encoding is only defined for scanlines of this length.
+            if (8..0x8000).contains(&self.image_width) {
+                line.extend_from_slice(&[
+                    2,
+                    2,
+                    (self.image_width >> 8) as u8,
+                    self.image_width as u8,
+                ]);
+                for component in 0..4 {
+                    let bytes: Vec<u8> = pixels.iter().map(|pixel| pixel[component]).collect();
+                    write_rle(&mut line, &bytes);
+                }
+            } else {
+                for pixel in pixels {
+                    line.extend_from_slice(&pixel);
+                }
+            }
+            out.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+impl HDR {
+    /// Decodes a Radiance image stored top to bottom, either flat or run length encoded.
+    pub fn decode(data: &[u8]) -> IoResult<HDR> {
+        let mut offset = 0;
+        let mut next_line = || -> IoResult<&[u8]> {
+            let len = data[offset..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .ok_or_else(|| invalid("truncated header"))?;
+            let line = &data[offset..offset + len];
+            offset += len + 1;
+            Ok(line)
+        };
+
+        if !next_line()?.starts_with(b"#?") {
+            return Err(invalid("not a Radiance file"));
+        }
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid(&format!(
+                    "unsupported {}",
+                    String::from_utf8_lossy(line)
+                )));
+            }
+        }
+        let resolution = String::from_utf8_lossy(next_line()?).to_string();
+        let (image_width, image_height) = match resolution.split_whitespace().collect::<Vec<_>>()[..]
+        {
+            ["-Y", height, "+X", width] => match (width.parse(), height.parse()) {
+                (Ok(width), Ok(height)) => (width, height),
+                _ => return Err(invalid(&format!("malformed resolution \"{}\"", resolution))),
+            },
+            _ => {
+                return Err(invalid(&format!(
+                    "unsupported orientation \"{}\"",
+                    resolution
+                )))
+            }
+        };
+
+        if image_width == 0 || image_height == 0 {
+            return Err(invalid("empty image"));
+        }
+        let width = image_width as usize;
+        // Check the data could hold that many pixels before making room for them: each line
+        // takes at least two bytes for every 127 pixels of each component when run length
+        // encoded, and four bytes a pixel otherwise.
+        let smallest_line = if (8..0x8000).contains(&width) {
+            4 + 8 * width.div_ceil(127)
+        } else {
+            4 * width
+        };
+        if smallest_line.saturating_mul(image_height as usize) > data.len() - offset {
+            return Err(invalid("truncated image data"));
+        }
+        let mut image_data = Vec::with_capacity(width * image_height as usize);
+        let mut line = vec![[0u8; 4]; width];
+        let mut bytes = data[offset..].iter().copied();
+        let mut next = || bytes.next().ok_or_else(|| invalid("truncated image data"));
+        for _ in 0..image_height {
+            let first = [next()?, next()?, next()?, next()?];
+            if (8..0x8000).contains(&width)
+                && first[..2] == [2, 2]
+                && ((first[2] as usize) << 8 | first[3] as usize) == width
+            {
+                // Each component is stored in turn, as a series of runs and literal spans
+                for component in 0..4 {
+                    let mut x = 0;
+                    while x < width {
+                        let count = next()? as usize;
+                        let (count, run) = if count > 128 {
+                            (count - 128, Some(next()?))
+                        } else {
+                            (count, None)
+                        };
+                        if count == 0 || x + count > width {
+                            return Err(invalid("corrupt run length encoding"));
+                        }
+                        for pixel in &mut line[x..x + count] {
+                            pixel[component] = match run {
+                                Some(byte) => byte,
+                                None => next()?,
+                            };
+                        }
+                        x += count;
+                    }
+                }
+            } else {
+                line[0] = first;
+                for pixel in &mut line[1..] {
+                    *pixel = [next()?, next()?, next()?, next()?];
+                }
+            }
+            image_data.extend(line.iter().map(from_rgbe));
+        }
+
+        Ok(HDR {
+            image_width,
+            image_height,
+            image_data,
+        })
+    }
+}
+
+fn to_rgbe(color: &Color) -> [u8; 4] {
+    let max = color.red.max(color.green).max(color.blue);
+    if max.is_nan() || max <= 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    let (mantissa, exponent) = frexp(max);
+    let scale = mantissa * 256.0 / max;
+    [
+        (color.red.max(0.0) * scale) as u8,
+        (color.green.max(0.0) * scale) as u8,
+        (color.blue.max(0.0) * scale) as u8,
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+fn from_rgbe(rgbe: &[u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - 128 - 8);
+    Color::new(
+        rgbe[0] as f64 * scale,
+        rgbe[1] as f64 * scale,
+        rgbe[2] as f64 * scale,
+    )
+}
+
+/// Splits `value` into a mantissa in [0.5, 1) and a power of two exponent.
+fn frexp(value: f64) -> (f64, i32) {
+    let exponent = value.log2().floor() as i32 + 1;
+    let mantissa = value / 2f64.powi(exponent);
+    // log2 can be off by one for values right at a power of two
+    if mantissa >= 1.0 {
+        (mantissa / 2.0, exponent + 1)
+    } else if mantissa < 0.5 {
+        (mantissa * 2.0, exponent - 1)
+    } else {
+        (mantissa, exponent)
+    }
+}
+
+/// Writes one component of a scanline as runs (a count above 128 followed by the repeated
+/// byte) and literal spans (a count of at most 128 followed by that many bytes).
+fn write_rle(image: &mut Vec<u8>, bytes: &[u8]) {
+    const MIN_RUN: usize = 4;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        // Find the next run worth encoding
+        let mut run_start = idx;
+        let mut run_len = 0;
+        while run_start < bytes.len() {
+            run_len = 1;
+            while run_start + run_len < bytes.len()
+                && run_len < 127
+                && bytes[run_start + run_len] == bytes[run_start]
+            {
+                run_len += 1;
+            }
+            if run_len >= MIN_RUN {
+                break;
+            }
+            run_start += run_len;
+        }
+        if run_len < MIN_RUN {
+            run_start = bytes.len();
+        }
+
+        // Everything before it is written literally
+        while idx < run_start {
+            let count = (run_start - idx).min(128);
+            image.push(count as u8);
+            image.extend_from_slice(&bytes[idx..idx + count]);
+            idx += count;
+        }
+
+        if run_start < bytes.len() {
+            image.push(128 + run_len as u8);
+            image.push(bytes[run_start]);
+            idx = run_start + run_len;
+        }
+    }
+}
+
+fn invalid(message: &str) -> IoError {
+    IoError::new(IoErrorKind::InvalidData, format!("HDR: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rgbe() {
+        assert_eq!(to_rgbe(&Color::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(&Color::new(12.0, 3.0, 0.75)), [192, 48, 12, 132]);
+        assert_eq!(to_rgbe(&Color::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Wide enough to be run length encoded, and too narrow to be
+        for image_width in [3, 20] {
+            let image_data: Vec<Color> = (0..image_width * 2)
+                .map(|idx| Color::new(idx as f64 / 4.0, 1.0, if idx < 10 { 0.0 } else { 96.0 }))
+                .collect();
+            let hdr = HDR {
+                image_width,
+                image_height: 2,
+                image_data: image_data.clone(),
+            };
+            let mut image = vec![];
+            hdr.write(&mut image).unwrap();
+            let decoded = HDR::decode(&image).unwrap();
+            assert_eq!(decoded.image_width, image_width);
+            assert_eq!(decoded.image_height, 2);
+            for (actual, expected) in decoded.image_data.iter().zip(image_data) {
+                // The shared exponent leaves 8 bits of precision relative to the brightest
+                // component
+                let tolerance = expected.red.max(expected.green).max(expected.blue) / 128.0;
+                assert!((actual.red - expected.red).abs() <= tolerance);
+                assert!((actual.green - expected.green).abs() <= tolerance);
+                assert!((actual.blue - expected.blue).abs() <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bad_resolution() {
+        let error = |resolution: &str| {
+            let header = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution);
+            HDR::decode(header.as_bytes()).err().unwrap().to_string()
+        };
+        assert_eq!(error("-Y 0 +X 16"), "HDR: empty image");
+        assert_eq!(error("-Y 16 +X 0"), "HDR: empty image");
+        assert_eq!(
+            error("-Y 4000000000 +X 4000000000"),
+            "HDR: truncated image data"
+        );
+    }
+
+    #[test]
+    fn test_rle() {
+        let mut bytes = vec![1, 2, 3];
+        bytes.extend_from_slice(&[9; 200]);
+        bytes.push(4);
+
+        let mut encoded = vec![];
+        write_rle(&mut encoded, &bytes);
+        assert_eq!(encoded, vec![3, 1, 2, 3, 255, 9, 128 + 73, 9, 1, 4]);
+    }
+}
@@ -1,11 +1,12 @@
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::BufWriter;
 
-pub trait Image<T> {
-    fn render(&self) -> Vec<u8>;
+pub trait Image {
+    fn write(&self, out: &mut dyn Write) -> std::io::Result<()>;
     fn render_to_file(&self, filename: &str) -> std::io::Result<()> {
-        let mut file = File::create(filename)?;
-        file.write_all(&self.render())?;
-        Ok(())
+        let mut file = BufWriter::new(File::create(filename)?);
+        self.write(&mut file)?;
+        file.flush()
     }
 }
@@ -1,4 +1,12 @@
 mod image;
 pub use image::Image;
+mod exr;
+pub use exr::EXR;
+mod hdr;
+pub use hdr::HDR;
+mod pfm;
+pub use pfm::PFM;
+mod png;
+pub use png::PNG;
 mod ppm;
-pub use ppm::PPM;
+pub use ppm::{PPMFormat, PPM};
@@ -0,0 +1,56 @@
+use crate::color::Color;
+use crate::image::Image;
+use std::io::prelude::*;
+
+/// Portable float map, storing linear radiance as 32 bit floats.
+pub struct PFM {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub image_data: Vec<Color>,
+}
+
+impl Image for PFM {
+    fn write(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        // A negative scale marks the data as little endian.
+        write!(
+            out,
+            "PF\n{} {}\n-1.0\n",
+            self.image_width, self.image_height
+        )?;
+
+        // Scanlines are stored from the bottom of the image up.
+        let mut line = vec![];
+        for row in self.image_data.chunks(self.image_width as usize).rev() {
+            line.clear();
+            for pixel in row {
+                for channel in &[pixel.red, pixel.green, pixel.blue] {
+                    line.extend_from_slice(&(*channel as f32).to_le_bytes());
+                }
+            }
+            out.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let pfm = PFM {
+            image_width: 1,
+            image_height: 2,
+            image_data: vec![Color::new(2.5, 0.0, 0.0), Color::new(0.0, 0.0, -1.0)],
+        };
+
+        let mut expected = b"PF\n1 2\n-1.0\n".to_vec();
+        for value in &[0.0f32, 0.0, -1.0, 2.5, 0.0, 0.0] {
+            expected.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut image = vec![];
+        pfm.write(&mut image).unwrap();
+        assert_eq!(image, expected);
+    }
+}
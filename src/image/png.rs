@@ -0,0 +1,351 @@
+use crate::color::Color;
+use crate::image::Image;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+use std::io::prelude::*;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+pub struct PNG {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub image_data: Vec<Color>,
+}
+
+impl Image for PNG {
+    fn write(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut image = SIGNATURE.to_vec();
+
+        let mut header = vec![];
+        header.extend_from_slice(&self.image_width.to_be_bytes());
+        header.extend_from_slice(&self.image_height.to_be_bytes());
+        // 8 bits per channel, RGB, default compression/filtering, no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_chunk(&mut image, b"IHDR", &header);
+
+        // Every scanline is prefixed with its filter type; we always use 0 (none).
+        let mut scanlines =
+            Vec::with_capacity(self.image_data.len() * 3 + self.image_height as usize);
+        for row in self.image_data.chunks(self.image_width as usize) {
+            scanlines.push(0);
+            for pixel in row {
+                let pixel = pixel.gamma_corrected();
+                scanlines.push(to_byte(pixel.hex_red()));
+                scanlines.push(to_byte(pixel.hex_green()));
+                scanlines.push(to_byte(pixel.hex_blue()));
+            }
+        }
+        write_chunk(&mut image, b"IDAT", &compress_to_vec_zlib(&scanlines, 6));
+        write_chunk(&mut image, b"IEND", &[]);
+        out.write_all(&image)
+    }
+}
+
+impl PNG {
+    /// Decodes a non-interlaced PNG with 8 or 16 bits per channel (or an 8 bit palette),
+    /// undoing the gamma applied by `write`. Alpha is ignored.
+    pub fn decode(data: &[u8]) -> IoResult<PNG> {
+        if data.len() < SIGNATURE.len() || data[..SIGNATURE.len()] != SIGNATURE {
+            return Err(invalid("not a PNG file"));
+        }
+
+        let mut header = None;
+        let mut palette = vec![];
+        let mut compressed = vec![];
+        let mut offset = SIGNATURE.len();
+        loop {
+            if offset + 12 > data.len() {
+                return Err(invalid("truncated chunk"));
+            }
+            let len = u32::from_be_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ]) as usize;
+            let kind = &data[offset + 4..offset + 8];
+            if offset + 12 + len > data.len() {
+                return Err(invalid("truncated chunk"));
+            }
+            let body = &data[offset + 8..offset + 8 + len];
+            let crc = &data[offset + 8 + len..offset + 12 + len];
+            if crc32(&data[offset + 4..offset + 8 + len]).to_be_bytes() != crc {
+                return Err(invalid("chunk checksum mismatch"));
+            }
+            offset += 12 + len;
+
+            match kind {
+                b"IHDR" => header = Some(Header::parse(body)?),
+                b"PLTE" => palette = body.to_vec(),
+                b"IDAT" => compressed.extend_from_slice(body),
+                b"IEND" => break,
+                _ => {}
+            }
+        }
+
+        let header = header.ok_or_else(|| invalid("missing IHDR chunk"))?;
+        let raw = decompress_to_vec_zlib(&compressed)
+            .map_err(|err| invalid(&format!("corrupt image data: {:?}", err)))?;
+        let samples = unfilter(&header, &raw)?;
+
+        let channels = header.channels();
+        let bytes_per_sample = header.bit_depth as usize / 8;
+        let max = ((1u32 << header.bit_depth) - 1) as f64;
+        let sample = |idx: usize| -> f64 {
+            let offset = idx * bytes_per_sample;
+            if bytes_per_sample == 2 {
+                u16::from_be_bytes([samples[offset], samples[offset + 1]]) as f64 / max
+            } else {
+                samples[offset] as f64 / max
+            }
+        };
+
+        let pixel_count = (header.width as usize)
+            .checked_mul(header.height as usize)
+            .ok_or_else(|| invalid("image too large"))?;
+        let mut image_data = Vec::with_capacity(pixel_count);
+        for pixel in 0..pixel_count {
+            let base = pixel * channels;
+            let color = match header.color_type {
+                // Grayscale, with or without alpha
+                0 | 4 => Color::new(sample(base), sample(base), sample(base)),
+                // Palette
+                3 => {
+                    let entry = samples[base] as usize * 3;
+                    if entry + 2 >= palette.len() {
+                        return Err(invalid("palette index out of range"));
+                    }
+                    Color::new(
+                        palette[entry] as f64 / 255.0,
+                        palette[entry + 1] as f64 / 255.0,
+                        palette[entry + 2] as f64 / 255.0,
+                    )
+                }
+                // RGB, with or without alpha
+                _ => Color::new(sample(base), sample(base + 1), sample(base + 2)),
+            };
+            image_data.push(color * color);
+        }
+
+        Ok(PNG {
+            image_width: header.width,
+            image_height: header.height,
+            image_data,
+        })
+    }
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+}
+
+impl Header {
+    fn parse(body: &[u8]) -> IoResult<Header> {
+        if body.len() != 13 {
+            return Err(invalid("malformed IHDR chunk"));
+        }
+        let header = Header {
+            width: u32::from_be_bytes([body[0], body[1], body[2], body[3]]),
+            height: u32::from_be_bytes([body[4], body[5], body[6], body[7]]),
+            bit_depth: body[8],
+            color_type: body[9],
+        };
+        let interlaced = body[12] != 0;
+
+        let supported = match header.color_type {
+            0 | 2 | 4 | 6 => header.bit_depth == 8 || header.bit_depth == 16,
+            3 => header.bit_depth == 8,
+            _ => false,
+        };
+        if !supported || interlaced {
+            return Err(invalid(&format!(
+                "unsupported format (color type {}, bit depth {}{})",
+                header.color_type,
+                header.bit_depth,
+                if interlaced { ", interlaced" } else { "" }
+            )));
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+}
+
+/// Reverses the per-scanline filters, returning the raw samples.
+fn unfilter(header: &Header, raw: &[u8]) -> IoResult<Vec<u8>> {
+    let bpp = header.channels() * header.bit_depth as usize / 8;
+    let height = header.height as usize;
+    let too_large = || invalid("image too large");
+    let stride = (header.width as usize)
+        .checked_mul(bpp)
+        .ok_or_else(too_large)?;
+    let size = (stride + 1).checked_mul(height).ok_or_else(too_large)?;
+    if raw.len() < size {
+        return Err(invalid("truncated image data"));
+    }
+
+    let mut samples = vec![0u8; stride * height];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (previous, current) = samples.split_at_mut(y * stride);
+        let previous = if y == 0 {
+            None
+        } else {
+            Some(&previous[(y - 1) * stride..])
+        };
+        let current = &mut current[..stride];
+
+        for x in 0..stride {
+            let a = if x >= bpp { current[x - bpp] } else { 0 };
+            let b = previous.map_or(0, |previous| previous[x]);
+            let c = match previous {
+                Some(previous) if x >= bpp => previous[x - bpp],
+                _ => 0,
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(invalid(&format!("unknown filter type {}", filter))),
+            };
+            current[x] = line[x].wrapping_add(predictor);
+        }
+    }
+    Ok(samples)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn write_chunk(image: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    image.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    let start = image.len();
+    image.extend_from_slice(kind);
+    image.extend_from_slice(body);
+    let crc = crc32(&image[start..]);
+    image.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn to_byte(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn invalid(message: &str) -> IoError {
+    IoError::new(IoErrorKind::InvalidData, format!("PNG: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let image_data = vec![
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.2, 0.4, 0.6),
+            Color::new(1.0, 1.0, 1.0),
+        ];
+        let png = PNG {
+            image_width: 3,
+            image_height: 2,
+            image_data: image_data.clone(),
+        };
+
+        let mut image = vec![];
+        png.write(&mut image).unwrap();
+        let decoded = PNG::decode(&image).unwrap();
+        assert_eq!(decoded.image_width, 3);
+        assert_eq!(decoded.image_height, 2);
+        for (actual, expected) in decoded.image_data.iter().zip(image_data) {
+            // Quantizing to 8 bits after gamma correction loses a little precision.
+            assert!((actual.red - expected.red).abs() < 0.01);
+            assert!((actual.green - expected.green).abs() < 0.01);
+            assert!((actual.blue - expected.blue).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_decode_filtered() {
+        let mut header = vec![0, 0, 0, 2, 0, 0, 0, 2];
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        // First row uses the Sub filter, second row the Up filter.
+        let scanlines = [1, 10, 20, 30, 5, 5, 5, 2, 2, 2, 2, 5, 5, 5];
+
+        let mut data = SIGNATURE.to_vec();
+        write_chunk(&mut data, b"IHDR", &header);
+        write_chunk(&mut data, b"IDAT", &compress_to_vec_zlib(&scanlines, 6));
+        write_chunk(&mut data, b"IEND", &[]);
+
+        let decoded = PNG::decode(&data).unwrap();
+        let bytes: Vec<i32> = decoded
+            .image_data
+            .iter()
+            .map(|pixel| pixel.gamma_corrected())
+            .flat_map(|pixel| vec![pixel.hex_red(), pixel.hex_green(), pixel.hex_blue()])
+            .collect();
+        assert_eq!(bytes, vec![10, 20, 30, 15, 25, 35, 12, 22, 32, 20, 30, 40]);
+    }
+
+    #[test]
+    fn test_huge_header() {
+        // 16 bit RGBA, as wide and tall as the header allows
+        let mut header = vec![0xff; 8];
+        header.extend_from_slice(&[16, 6, 0, 0, 0]);
+        let mut data = SIGNATURE.to_vec();
+        write_chunk(&mut data, b"IHDR", &header);
+        write_chunk(&mut data, b"IDAT", &compress_to_vec_zlib(&[0; 16], 6));
+        write_chunk(&mut data, b"IEND", &[]);
+        assert_eq!(
+            PNG::decode(&data).err().unwrap().to_string(),
+            "PNG: image too large"
+        );
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+}
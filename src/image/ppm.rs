@@ -1,33 +1,257 @@
 use crate::color::Color;
 use crate::image::Image;
-use itertools::Itertools;
+use std::io::prelude::*;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PPMFormat {
+    /// Plain text (P3)
+    Ascii,
+    /// Raw bytes (P6) with 8 bits per channel
+    Binary,
+    /// Raw bytes (P6) with 16 bit, big endian channels
+    Binary16,
+}
 
 pub struct PPM {
     pub image_width: u32,
     pub image_height: u32,
     pub image_data: Vec<Color>,
+    pub format: PPMFormat,
+}
+
+impl Image for PPM {
+    fn write(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let (magic, max_value) = match self.format {
+            PPMFormat::Ascii => ("P3", 255),
+            PPMFormat::Binary => ("P6", 255),
+            PPMFormat::Binary16 => ("P6", 65535),
+        };
+        write!(
+            out,
+            "{}\n{} {}\n{}\n",
+            magic, self.image_width, self.image_height, max_value
+        )?;
+
+        let mut line = vec![];
+        for row in self.image_data.chunks(self.image_width as usize) {
+            line.clear();
+            for pixel in row {
+                let pixel = pixel.gamma_corrected();
+                match self.format {
+                    PPMFormat::Ascii => writeln!(
+                        line,
+                        "{} {} {}",
+                        pixel.hex_red(),
+                        pixel.hex_green(),
+                        pixel.hex_blue()
+                    )?,
+                    PPMFormat::Binary => line.extend_from_slice(&[
+                        pixel.hex_red() as u8,
+                        pixel.hex_green() as u8,
+                        pixel.hex_blue() as u8,
+                    ]),
+                    PPMFormat::Binary16 => {
+                        for channel in &[pixel.red, pixel.green, pixel.blue] {
+                            let value = (channel * 65535.0).round() as u16;
+                            line.extend_from_slice(&value.to_be_bytes());
+                        }
+                    }
+                }
+            }
+            out.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+impl PPM {
+    /// Decodes a plain (P3) or raw (P6) PPM, undoing the gamma applied by `write`.
+    pub fn decode(data: &[u8]) -> IoResult<PPM> {
+        let mut header = Header { data, offset: 0 };
+        let magic = header.next_token()?.to_vec();
+        if magic != b"P3" && magic != b"P6" {
+            return Err(invalid("not a P3 or P6 file"));
+        }
+        let image_width = header.next_number()?;
+        let image_height = header.next_number()?;
+        let max_value = header.next_number()?;
+        if max_value == 0 || max_value > 65535 {
+            return Err(invalid(&format!("unsupported maximum value {}", max_value)));
+        }
+
+        let samples = (image_width as usize)
+            .checked_mul(image_height as usize)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .ok_or_else(|| invalid("image too large"))?;
+        // Every sample takes up at least a byte, so don't make room for more than are left
+        if samples > data.len() - header.offset {
+            return Err(invalid("truncated image data"));
+        }
+        let mut values = Vec::with_capacity(samples);
+        let format = if magic == b"P3" {
+            for _ in 0..samples {
+                values.push(header.next_number()?);
+            }
+            PPMFormat::Ascii
+        } else {
+            // A single whitespace character separates the header from the raw samples
+            let start = header.offset + 1;
+            let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+            let raw = data
+                .get(start..start + samples * bytes_per_sample)
+                .ok_or_else(|| invalid("truncated image data"))?;
+            if bytes_per_sample == 1 {
+                values.extend(raw.iter().map(|byte| *byte as u32));
+                PPMFormat::Binary
+            } else {
+                values.extend(
+                    raw.chunks(2)
+                        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as u32),
+                );
+                PPMFormat::Binary16
+            }
+        };
+
+        let max = max_value as f64;
+        let image_data = values
+            .chunks(3)
+            .map(|rgb| {
+                let color = Color::new(
+                    rgb[0].min(max_value) as f64 / max,
+                    rgb[1].min(max_value) as f64 / max,
+                    rgb[2].min(max_value) as f64 / max,
+                );
+                color * color
+            })
+            .collect();
+
+        Ok(PPM {
+            image_width,
+            image_height,
+            image_data,
+            format,
+        })
+    }
+}
+
+/// Reads the whitespace separated fields of the header, and plain text samples, skipping over
+/// comments.
+struct Header<'a> {
+    data: &'a [u8],
+    offset: usize,
 }
 
-impl Image<String> for PPM {
-    fn render(&self) -> Vec<u8> {
-        let mut image = String::default();
-        image.push_str(&format!(
-            "P3\n{} {}\n255\n",
-            self.image_width, self.image_height
-        ));
-        for row in &self.image_data.iter().chunks(self.image_width as usize) {
-            image.push_str(&format!(
-                "{}\n",
-                row.map(|pixel| format!(
-                    "{} {} {}",
-                    pixel.hex_red(),
-                    pixel.hex_green(),
-                    pixel.hex_blue(),
+impl<'a> Header<'a> {
+    fn next_token(&mut self) -> IoResult<&'a [u8]> {
+        let data = self.data;
+        loop {
+            match data.get(self.offset) {
+                Some(b'#') => {
+                    while data.get(self.offset).is_some_and(|byte| *byte != b'\n') {
+                        self.offset += 1;
+                    }
+                }
+                Some(byte) if byte.is_ascii_whitespace() => self.offset += 1,
+                Some(_) => break,
+                None => return Err(invalid("truncated file")),
+            }
+        }
+        let start = self.offset;
+        while data
+            .get(self.offset)
+            .is_some_and(|byte| !byte.is_ascii_whitespace())
+        {
+            self.offset += 1;
+        }
+        Ok(&data[start..self.offset])
+    }
+
+    fn next_number(&mut self) -> IoResult<u32> {
+        let token = self.next_token()?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| {
+                invalid(&format!(
+                    "malformed number \"{}\"",
+                    String::from_utf8_lossy(token)
                 ))
-                .intersperse("\n".to_string())
-                .join("")
-            ));
+            })
+    }
+}
+
+fn invalid(message: &str) -> IoError {
+    IoError::new(IoErrorKind::InvalidData, format!("PPM: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: PPMFormat) -> Vec<u8> {
+        let ppm = PPM {
+            image_width: 2,
+            image_height: 1,
+            image_data: vec![Color::new(1.0, 0.25, 0.0), Color::new(0.0, 0.0, 1.0)],
+            format,
+        };
+        let mut image = vec![];
+        ppm.write(&mut image).unwrap();
+        image
+    }
+
+    #[test]
+    fn test_ascii() {
+        assert_eq!(
+            String::from_utf8(render(PPMFormat::Ascii)).unwrap(),
+            "P3\n2 1\n255\n255 128 0\n0 0 255\n"
+        );
+    }
+
+    #[test]
+    fn test_binary() {
+        let mut expected = b"P6\n2 1\n255\n".to_vec();
+        expected.extend_from_slice(&[255, 128, 0, 0, 0, 255]);
+        assert_eq!(render(PPMFormat::Binary), expected);
+
+        let mut expected = b"P6\n2 1\n65535\n".to_vec();
+        expected.extend_from_slice(&[255, 255, 128, 0, 0, 0, 0, 0, 0, 0, 255, 255]);
+        assert_eq!(render(PPMFormat::Binary16), expected);
+    }
+
+    #[test]
+    fn test_decode() {
+        for format in [PPMFormat::Ascii, PPMFormat::Binary, PPMFormat::Binary16] {
+            let decoded = PPM::decode(&render(format)).unwrap();
+            assert_eq!(decoded.format, format);
+            assert_eq!((decoded.image_width, decoded.image_height), (2, 1));
+            assert!((decoded.image_data[0].green - 0.25).abs() < 0.01);
+            assert_eq!(decoded.image_data[1], Color::new(0.0, 0.0, 1.0));
         }
-        image.as_bytes().to_owned()
+
+        let decoded = PPM::decode(b"P3 # a comment\n1 1 # and another\n4\n4 2 0").unwrap();
+        assert_eq!(decoded.image_data[0], Color::new(1.0, 0.25, 0.0));
+        assert_eq!(
+            PPM::decode(b"P3\n1 1\n255\n255 0")
+                .err()
+                .unwrap()
+                .to_string(),
+            "PPM: truncated file"
+        );
+        assert_eq!(
+            PPM::decode(b"P6\n4294967295 4294967295\n255\n\0\0\0")
+                .err()
+                .unwrap()
+                .to_string(),
+            "PPM: image too large"
+        );
+        assert_eq!(
+            PPM::decode(b"P6\n100000 100000\n255\n\0\0\0")
+                .err()
+                .unwrap()
+                .to_string(),
+            "PPM: truncated image data"
+        );
     }
 }
@@ -0,0 +1,30 @@
+use crate::color::Color;
+use crate::integrator::{first_hit, Integrator};
+use crate::ray::Ray;
+use crate::scene::Scene;
+use rand::RngCore;
+
+/// Shows the color of whatever each ray hits first, without any lighting: how much a surface
+/// keeps of the light that scatters off it, what a light gives off, or the background.
+pub struct Albedo;
+
+impl Integrator for Albedo {
+    fn ray_color(&self, ray: &Ray, scene: &Scene, rng: &mut dyn RngCore) -> Color {
+        let hit_record = match first_hit(ray, scene, f64::INFINITY, rng) {
+            Some(hit_record) => hit_record,
+            None => return scene.background.color(ray),
+        };
+        let mut attenuation = Color::default();
+        let mut scattered = *ray;
+        if hit_record
+            .material
+            .scatter(ray, &hit_record, &mut attenuation, &mut scattered, rng)
+        {
+            attenuation
+        } else {
+            hit_record
+                .material
+                .emitted(hit_record.u, hit_record.v, &hit_record.p)
+        }
+    }
+}
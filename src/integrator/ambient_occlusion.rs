@@ -0,0 +1,86 @@
+use crate::color::Color;
+use crate::integrator::{first_hit, Integrator};
+use crate::ray::Ray;
+use crate::scene::Scene;
+use crate::vec3::Vec3;
+use rand::RngCore;
+
+/// Shades each surface by how much of the sky above it is open, ignoring materials and lights.
+pub struct AmbientOcclusion {
+    distance: f64,
+}
+
+impl AmbientOcclusion {
+    /// Only objects closer than `distance` to a surface shade it.
+    pub fn new(distance: f64) -> AmbientOcclusion {
+        AmbientOcclusion { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn ray_color(&self, ray: &Ray, scene: &Scene, rng: &mut dyn RngCore) -> Color {
+        let hit_record = match first_hit(ray, scene, f64::INFINITY, rng) {
+            Some(hit_record) => hit_record,
+            None => return Color::new(1.0, 1.0, 1.0),
+        };
+        // Cosine weighted, so that the average is the fraction of light an open sky would give
+        let mut direction = hit_record.normal + Vec3::random_unit_vector(rng);
+        if direction.near_zero() {
+            direction = hit_record.normal;
+        }
+        let occlusion_ray = Ray::new(hit_record.p, Vec3::unit_vector(&direction), ray.time);
+        match first_hit(&occlusion_ray, scene, self.distance, rng) {
+            Some(_) => Color::new(0.0, 0.0, 0.0),
+            None => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::object::{ObjectList, Quad};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_ambient_occlusion() {
+        // A floor with a low ceiling over half of it
+        let grey = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let scene = Scene::new(&mut ObjectList::new(vec![
+            Arc::new(Quad::new(
+                Vec3::new(-10.0, 0.0, -10.0),
+                Vec3::new(20.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 20.0),
+                grey.clone(),
+            )),
+            Arc::new(Quad::new(
+                Vec3::new(-100.0, 1.0, -100.0),
+                Vec3::new(100.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 200.0),
+                grey,
+            )),
+        ]));
+        let mut rng = StdRng::seed_from_u64(1);
+        let down = |x: f64| Ray::new(Vec3::new(x, 0.5, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+
+        let open = AmbientOcclusion::new(f64::INFINITY);
+        assert_eq!(
+            open.ray_color(&down(5.0), &scene, &mut rng),
+            Color::new(1.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            open.ray_color(&down(-5.0), &scene, &mut rng),
+            Color::new(0.0, 0.0, 0.0)
+        );
+        // Within 2 units, the ceiling only blocks the 3/4 of the light within 60° of straight up
+        let near = AmbientOcclusion::new(2.0);
+        let mut open_sky = 0.0;
+        for _ in 0..1000 {
+            open_sky += near.ray_color(&down(-5.0), &scene, &mut rng).red;
+        }
+        assert!((open_sky / 1000.0 - 0.25).abs() < 0.05);
+    }
+}
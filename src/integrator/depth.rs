@@ -0,0 +1,28 @@
+use crate::color::Color;
+use crate::integrator::{first_hit, Integrator};
+use crate::ray::Ray;
+use crate::scene::Scene;
+use rand::RngCore;
+
+/// Shows how far away whatever each ray hits first is, from black up close to white at `far`
+/// and beyond.
+pub struct Depth {
+    far: f64,
+}
+
+impl Depth {
+    pub fn new(far: f64) -> Depth {
+        Depth { far }
+    }
+}
+
+impl Integrator for Depth {
+    fn ray_color(&self, ray: &Ray, scene: &Scene, rng: &mut dyn RngCore) -> Color {
+        let distance = match first_hit(ray, scene, f64::INFINITY, rng) {
+            Some(hit_record) => hit_record.t * ray.direction.len(),
+            None => f64::INFINITY,
+        };
+        let shade = (distance / self.far).min(1.0);
+        Color::new(shade, shade, shade)
+    }
+}
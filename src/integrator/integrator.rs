@@ -0,0 +1,22 @@
+use crate::color::Color;
+use crate::material::Metal;
+use crate::object::{HitRecord, Object};
+use crate::ray::Ray;
+use crate::scene::Scene;
+use rand::RngCore;
+use std::sync::Arc;
+
+/// Works out how much light comes back along a camera ray.
+pub trait Integrator {
+    fn ray_color(&self, ray: &Ray, scene: &Scene, rng: &mut dyn RngCore) -> Color;
+}
+
+/// The nearest thing the ray hits closer than `t_max`, if anything.
+pub fn first_hit(ray: &Ray, scene: &Scene, t_max: f64, rng: &mut dyn RngCore) -> Option<HitRecord> {
+    let mut hit_record = HitRecord::new(Arc::new(Metal::new(Color::default(), 1.0)));
+    if scene.hit(ray, 0.001, t_max, &mut hit_record, rng) {
+        Some(hit_record)
+    } else {
+        None
+    }
+}
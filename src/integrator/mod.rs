@@ -0,0 +1,20 @@
+mod integrator;
+pub use integrator::{first_hit, Integrator};
+
+mod path_tracer;
+pub use path_tracer::PathTracer;
+
+mod ambient_occlusion;
+pub use ambient_occlusion::AmbientOcclusion;
+
+mod normals;
+pub use normals::Normals;
+
+mod depth;
+pub use depth::Depth;
+
+mod albedo;
+pub use albedo::Albedo;
+
+mod traversal_cost;
+pub use traversal_cost::TraversalCost;
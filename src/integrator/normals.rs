@@ -0,0 +1,29 @@
+use crate::color::Color;
+use crate::integrator::{first_hit, Integrator};
+use crate::ray::Ray;
+use crate::scene::Scene;
+use rand::RngCore;
+
+/// Shows the outward surface normal of whatever each ray hits first, with each axis mapped from
+/// -1..1 to 0..1, and black where it misses.
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn ray_color(&self, ray: &Ray, scene: &Scene, rng: &mut dyn RngCore) -> Color {
+        match first_hit(ray, scene, f64::INFINITY, rng) {
+            Some(hit_record) => {
+                let normal = if hit_record.front_face {
+                    hit_record.normal
+                } else {
+                    -hit_record.normal
+                };
+                Color::new(
+                    0.5 * (normal.x + 1.0),
+                    0.5 * (normal.y + 1.0),
+                    0.5 * (normal.z + 1.0),
+                )
+            }
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
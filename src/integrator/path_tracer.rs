@@ -0,0 +1,283 @@
+use crate::color::Color;
+use crate::integrator::Integrator;
+use crate::material::Metal;
+use crate::object::{HitRecord, Object};
+use crate::ray::Ray;
+use crate::scene::Scene;
+use crate::vec3::Vec3;
+use rand::{Rng, RngCore};
+use std::sync::Arc;
+
+/// How much weight to give a sample taken with density `pdf`, when the same light could also
+/// have been found by a strategy with density `other_pdf` (Veach's power heuristic).
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+/// Follows rays as they bounce around the scene, adding up the light they find along the way.
+pub struct PathTracer {
+    max_depth: u32,
+    light_sampling: bool,
+}
+
+impl PathTracer {
+    /// Aims a ray at the lights from every diffuse bounce, as well as bouncing at random, for up
+    /// to `max_depth` bounces.
+    pub fn new(max_depth: u32) -> PathTracer {
+        PathTracer {
+            max_depth,
+            light_sampling: true,
+        }
+    }
+
+    /// Only finds lights by bouncing into them, which is much noisier for small lights.
+    pub fn naive(max_depth: u32) -> PathTracer {
+        PathTracer {
+            max_depth,
+            light_sampling: false,
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn ray_color(&self, ray: &Ray, scene: &Scene, rng: &mut dyn RngCore) -> Color {
+        let mut depth = self.max_depth;
+        let mut hit_record = HitRecord::new(Arc::new(Metal::new(Color::default(), 1.0)));
+        let mut light_record = hit_record.clone();
+        let mut ray = *ray;
+
+        let origin = Vec3::origin();
+        let black = Color::new(0.0, 0.0, 0.0);
+        // The light gathered along the path so far, and how much of the light found further
+        // along it will make it back to the camera.
+        let mut color = black;
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut scattered = Ray::new(origin, origin, 0.0);
+        let mut attenuation = Color::default();
+        // The density with which the last bounce picked the ray's direction, or 0 if the ray came
+        // from the camera or a mirror-like bounce, which sampling the lights can't do any better.
+        let mut bounce_pdf = 0.0;
+
+        loop {
+            if depth == 0 {
+                return color;
+            }
+            let bounce_origin = ray.origin;
+            if !scene.hit(&ray, 0.001, f64::INFINITY, &mut hit_record, rng) {
+                return color + throughput * scene.background.color(&ray);
+            }
+            let material = hit_record.material.clone();
+
+            // Lights that were also sampled directly from the last bounce only count in part
+            let emitted = material.emitted(hit_record.u, hit_record.v, &hit_record.p);
+            if self.light_sampling && bounce_pdf > 0.0 {
+                let light_pdf = scene.light_pdf(&bounce_origin, &ray.direction);
+                color += throughput * emitted * power_heuristic(bounce_pdf, light_pdf);
+            } else {
+                color += throughput * emitted;
+            }
+
+            if !material.scatter(&ray, &hit_record, &mut attenuation, &mut scattered, rng) {
+                return color;
+            }
+            if material.scattering_pdf(&ray, &hit_record, &scattered) <= 0.0 {
+                // Mirrors and glass only scatter one way, so there's nothing to sample
+                throughput *= attenuation;
+                bounce_pdf = 0.0;
+                ray = scattered;
+                depth -= 1;
+                continue;
+            }
+
+            // Aim half of the diffuse bounces at the brightest parts of the background, if it has
+            // any, weighting by the density of both strategies combined so that the average is
+            // unchanged.
+            let environment_direction = scene.background.sample_direction(rng);
+            let bounce_density = |direction: &Ray| {
+                let material_pdf = material.scattering_pdf(&ray, &hit_record, direction);
+                match environment_direction {
+                    Some(_) => {
+                        0.5 * material_pdf + 0.5 * scene.background.pdf(&direction.direction)
+                    }
+                    None => material_pdf,
+                }
+            };
+
+            // Next event estimation: look for light straight from one of the lights
+            let light_direction = if self.light_sampling {
+                scene.sample_light(&hit_record.p, rng)
+            } else {
+                None
+            };
+            if let Some(direction) = light_direction {
+                let to_light = Ray::new(hit_record.p, direction, ray.time);
+                let light_pdf = scene.light_pdf(&hit_record.p, &direction);
+                let bsdf = material.eval(&ray, &hit_record, &to_light);
+                if light_pdf > 0.0
+                    && bsdf != black
+                    && scene.hit(&to_light, 0.001, f64::INFINITY, &mut light_record, rng)
+                {
+                    let light = light_record.material.emitted(
+                        light_record.u,
+                        light_record.v,
+                        &light_record.p,
+                    );
+                    let weight = power_heuristic(light_pdf, bounce_density(&to_light));
+                    color += throughput * bsdf * light * (weight / light_pdf);
+                }
+            }
+
+            if let Some(direction) = environment_direction {
+                if rng.gen_bool(0.5) {
+                    scattered = Ray::new(hit_record.p, direction, ray.time);
+                }
+                let material_pdf = material.scattering_pdf(&ray, &hit_record, &scattered);
+                if material_pdf <= 0.0 {
+                    return color;
+                }
+                attenuation *= material_pdf / bounce_density(&scattered);
+            }
+            bounce_pdf = bounce_density(&scattered);
+            throughput *= attenuation;
+            ray = scattered;
+            depth -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::background::{Background, Constant, Environment};
+    use crate::image::HDR;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::object::{ObjectList, Quad, Sphere};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_emission() {
+        // A light seen directly, and a white diffuse sphere lit only by it
+        let light = Arc::new(DiffuseLight::new(Color::new(4.0, 2.0, 1.0)));
+        let white = Arc::new(Lambertian::new(Color::new(1.0, 1.0, 1.0)));
+        let mut scene = Scene::new(&mut ObjectList::new(vec![
+            Arc::new(Sphere::new(Vec3::new(0.0, 0.0, -2.0), 0.5, light)),
+            Arc::new(Sphere::new(Vec3::new(0.0, 0.0, 2.0), 0.5, white)),
+        ]));
+        scene.background = Box::new(Constant::new(Color::new(0.0, 0.0, 0.0)));
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let towards = |z: f64| Ray::new(Vec3::origin(), Vec3::new(0.0, 0.0, z), 0.0);
+        for integrator in &[PathTracer::new(10), PathTracer::naive(10)] {
+            assert_eq!(
+                integrator.ray_color(&towards(-1.0), &scene, &mut rng),
+                Color::new(4.0, 2.0, 1.0)
+            );
+            let mut lit = Color::new(0.0, 0.0, 0.0);
+            for _ in 0..100 {
+                lit += integrator.ray_color(&towards(1.0), &scene, &mut rng);
+            }
+            assert!(lit.red > 0.0 && lit.red == 4.0 * lit.blue);
+            let away = Ray::new(Vec3::origin(), Vec3::new(1.0, 0.0, 0.0), 0.0);
+            assert_eq!(
+                integrator.ray_color(&away, &scene, &mut rng),
+                Color::new(0.0, 0.0, 0.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_light_sampling() {
+        // A small round light above a grey floor, which a ray straight down rarely finds by
+        // bouncing alone
+        let light = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+        let grey = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut scene = Scene::new(&mut ObjectList::new(vec![
+            Arc::new(Quad::new(
+                Vec3::new(-100.0, 0.0, -100.0),
+                Vec3::new(200.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 200.0),
+                grey,
+            )),
+            Arc::new(Sphere::new(Vec3::new(0.0, 2.0, 0.0), 0.25, light)),
+        ]));
+        scene.background = Box::new(Constant::new(Color::new(0.0, 0.0, 0.0)));
+        assert_eq!(scene.lights.len(), 1);
+
+        let down = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut average = |integrator: &PathTracer, samples: usize| {
+            let mut total = 0.0;
+            for _ in 0..samples {
+                total += integrator.ray_color(&down, &scene, &mut rng).red;
+            }
+            total / samples as f64
+        };
+        // The sphere lights the floor below it with π·L·(r/h)², of which ρ/π is reflected back up
+        let expected = 0.5 * 4.0 * (0.25f64 / 2.0).powi(2);
+        assert!((average(&PathTracer::new(10), 2000) - expected).abs() < 0.02 * expected);
+        // Only about one bounce in 64 finds the light without aiming for it
+        assert!((average(&PathTracer::naive(10), 20000) - expected).abs() < 0.2 * expected);
+    }
+
+    #[test]
+    fn test_environment_sampling() {
+        // A sun low in the sky, and the same map without importance sampling
+        struct Unsampled(Environment);
+        impl Background for Unsampled {
+            fn color(&self, ray: &Ray) -> Color {
+                self.0.color(ray)
+            }
+        }
+        let sky = || {
+            let mut image_data = vec![Color::new(0.2, 0.3, 0.5); 32 * 16];
+            image_data[6 * 32 + 20] = Color::new(5000.0, 5000.0, 4000.0);
+            let image = HDR {
+                image_width: 32,
+                image_height: 16,
+                image_data,
+            };
+            Environment::new(image, 0.0, 1.0)
+        };
+
+        let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut scene = Scene::new(&mut ObjectList::new(vec![Arc::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            ground,
+        ))]));
+        let down = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut average = |scene: &Scene| {
+            let samples = 20000;
+            let mut total = Color::new(0.0, 0.0, 0.0);
+            let mut brightest: f64 = 0.0;
+            for _ in 0..samples {
+                let color = PathTracer::new(10).ray_color(&down, scene, &mut rng);
+                brightest = brightest.max(color.red);
+                total += color;
+            }
+            (total.red / samples as f64, brightest)
+        };
+
+        scene.background = Box::new(sky());
+        let (sampled, sampled_brightest) = average(&scene);
+        scene.background = Box::new(Unsampled(sky()));
+        let (unsampled, unsampled_brightest) = average(&scene);
+
+        // Half the light from a dim sky, plus the sun's pixel, which spans 1/32 of the way
+        // round and from 67.5 to 78.75 degrees from straight up.
+        let (near, far) = (67.5f64.to_radians(), 78.75f64.to_radians());
+        let sun = (5000.0 - 0.2) * (far.sin().powi(2) - near.sin().powi(2)) / 32.0;
+        let expected = 0.5 * (0.2 + sun);
+        assert!((sampled - expected).abs() < 0.05 * expected);
+        // Hitting the sun by chance is rare, and very bright when it happens
+        assert!(unsampled > 0.0);
+        assert!(sampled_brightest * 10.0 < unsampled_brightest);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::color::Color;
+use crate::integrator::{first_hit, Integrator};
+use crate::object::nodes_visited;
+use crate::ray::Ray;
+use crate::scene::Scene;
+use rand::RngCore;
+
+/// A heatmap of how many bounding volume nodes each camera ray visits to find what it hits,
+/// running from blue for none through green to red for `max_cost` or more.
+pub struct TraversalCost {
+    max_cost: f64,
+}
+
+impl TraversalCost {
+    pub fn new(max_cost: usize) -> TraversalCost {
+        TraversalCost {
+            max_cost: max_cost as f64,
+        }
+    }
+}
+
+impl Integrator for TraversalCost {
+    fn ray_color(&self, ray: &Ray, scene: &Scene, rng: &mut dyn RngCore) -> Color {
+        let before = nodes_visited();
+        first_hit(ray, scene, f64::INFINITY, rng);
+        let cost = ((nodes_visited() - before) as f64 / self.max_cost).min(1.0);
+        if cost < 0.5 {
+            Color::new(0.0, 2.0 * cost, 1.0 - 2.0 * cost)
+        } else {
+            Color::new(2.0 * cost - 1.0, 2.0 - 2.0 * cost, 0.0)
+        }
+    }
+}
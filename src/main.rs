@@ -1,94 +1,46 @@
+#![allow(clippy::module_inception, clippy::upper_case_acronyms)]
+
+use crate::background::Environment;
 use crate::camera::Camera;
 use crate::color::Color;
-use crate::image::{Image, PPM};
+use crate::image::{Image, PPMFormat, EXR, HDR, PFM, PNG, PPM};
+use crate::integrator::{
+    Albedo, AmbientOcclusion, Depth, Integrator, Normals, PathTracer, TraversalCost,
+};
 use crate::material::{Dielectric, Lambertian, Metal};
-use crate::object::{HitRecord, Object, ObjectList, Sphere};
-use crate::ray::Ray;
-use crate::scene::Scene;
+use crate::object::{Fog, Object, ObjectList, Sphere, DEFAULT_FOG_EXTENT};
+use crate::scene::{dump_scene_to_file, load_scene, Scene};
 use crate::vec3::Vec3;
 use clap::App;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
 use rayon::prelude::*;
+use std::fmt;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[macro_use]
 extern crate clap;
 
 mod aabb;
+mod background;
 mod camera;
 mod color;
 mod image;
+mod integrator;
 mod material;
+mod matrix;
+mod obj;
 mod object;
 mod ray;
 mod scene;
+mod texture;
 mod vec3;
 
-fn hit_sphere(center: &Vec3, radius: f64, r: &Ray) -> f64 {
-    let oc = r.origin - center.clone();
-    let a = r.direction.len_squared();
-    let half_b = Vec3::dot(&oc, &r.direction);
-    let c = oc.len_squared() - radius * radius;
-    let discriminant = half_b * half_b - a * c;
-
-    if discriminant < 0.0 {
-        -1.0
-    } else {
-        (-half_b - discriminant.sqrt()) / a
-    }
-}
-
-fn ray_color(ray: &Ray, scene: &Scene, mut depth: u32) -> Color {
-    let mut hit_record = HitRecord {
-        p: Vec3::origin(),
-        normal: Vec3::origin(),
-        t: 0.0,
-        front_face: false,
-        material: Arc::new(Metal::new(Color::default(), 1.0)),
-    };
-    let mut ray = ray.clone();
-
-    let origin = Vec3::origin();
-    let black = Color::new(0.0, 0.0, 0.0);
-    let mut color = Color::new(1.0, 1.0, 1.0);
-    let mut scattered = Ray::new(origin, origin);
-    let mut attenuation = Color::default();
-    let center = Vec3::new(0.0, 0.0, -1.0);
-
-    loop {
-        if depth <= 0 {
-            color *= black;
-            return color;
-        }
-        if scene.hit(&ray, 0.001, f64::INFINITY, &mut hit_record) {
-            if hit_record
-                .material
-                .scatter(&ray, &hit_record, &mut attenuation, &mut scattered)
-            {
-                color *= attenuation;
-                ray = scattered.clone();
-                depth -= 1;
-                continue;
-            }
-            color += black;
-            return color;
-        }
-        let t = hit_sphere(&center, 0.5, &ray);
-        if t > 0.0 {
-            let n = Vec3::unit_vector(&(ray.at(t) - center));
-
-            color *= 0.5 * &Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0);
-            return color;
-        }
-        let unit_direction = Vec3::unit_vector(&ray.direction);
-        let t = 0.5 * (unit_direction.y + 1.0);
-        color *= (1.0 - t) * &black + t * &Color::new(0.5, 0.7, 1.0);
-        return color;
-    }
-}
-
-fn random_scene() -> Scene {
+fn random_scene(rng: &mut dyn RngCore) -> Scene {
     let mut scene_objects: Vec<Arc<dyn Object + Send + Sync>> = vec![];
 
     let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
@@ -100,22 +52,22 @@ fn random_scene() -> Scene {
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = rand::thread_rng().gen_range(0.0..1.0);
-            let rand_num1 = rand::thread_rng().gen_range(0.0..1.0);
-            let rand_num2 = rand::thread_rng().gen_range(0.0..1.0);
+            let choose_mat = rng.gen_range(0.0..1.0);
+            let rand_num1 = rng.gen_range(0.0..1.0);
+            let rand_num2 = rng.gen_range(0.0..1.0);
 
             let center = Vec3::new(a as f64 + 0.9 * rand_num1, 0.2, b as f64 + 0.9 * rand_num2);
 
             if (center - Vec3::new(4.0, 0.2, 0.0)).len() > 0.9 {
                 if choose_mat < 0.8 {
                     // diffuse
-                    let albedo = Color::random(0.0, 1.0) * Color::random(0.0, 1.0);
+                    let albedo = Color::random(rng, 0.0, 1.0) * Color::random(rng, 0.0, 1.0);
                     let sphere_material = Arc::new(Lambertian::new(albedo));
                     scene_objects.push(Arc::new(Sphere::new(center, 0.2, sphere_material)));
                 } else if choose_mat < 0.95 {
                     // metal
-                    let albedo = Color::random(0.5, 1.0);
-                    let fuzz = rand::thread_rng().gen_range(0.0..0.5);
+                    let albedo = Color::random(rng, 0.5, 1.0);
+                    let fuzz = rng.gen_range(0.0..0.5);
                     let sphere_material = Arc::new(Metal::new(albedo, fuzz));
                     scene_objects.push(Arc::new(Sphere::new(center, 0.2, sphere_material)));
                 } else {
@@ -151,7 +103,162 @@ fn random_scene() -> Scene {
     Scene::new(&mut ObjectList::new(scene_objects))
 }
 
-fn main() -> IoResult<()> {
+/// Gives each pixel its own random stream, keyed on the seed and the pixel's index, so the
+/// result doesn't depend on which thread happens to render which pixel.
+fn pixel_rng(seed: u64, idx: usize) -> StdRng {
+    let mut key = [0; 32];
+    key[..8].copy_from_slice(&seed.to_le_bytes());
+    key[8..16].copy_from_slice(&(idx as u64).to_le_bytes());
+    StdRng::from_seed(key)
+}
+
+/// What it took to render an image, reported with `--stats`.
+struct RenderStats {
+    build_time: Duration,
+    samples: u64,
+    rays: u64,
+    nodes_visited: u64,
+    render_time: Duration,
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "built trees in {:.2?}, rendered {} samples in {:.2?} ({:.2} million rays a second), \
+             visiting {:.1} tree nodes per sample",
+            self.build_time,
+            self.samples,
+            self.render_time,
+            self.rays as f64 / self.render_time.as_secs_f64() / 1e6,
+            self.nodes_visited as f64 / self.samples.max(1) as f64
+        )
+    }
+}
+
+fn render(
+    camera: &Camera,
+    scene: &Scene,
+    image_width: u32,
+    image_height: u32,
+    samples_per_pixel: u32,
+    integrator: &(dyn Integrator + Sync),
+    seed: u64,
+) -> (Vec<Color>, RenderStats) {
+    let image_data_size = (image_width * image_height) as usize;
+    let start = Instant::now();
+    let nodes_visited = AtomicU64::new(0);
+    let rays = AtomicU64::new(0);
+
+    let image_data = (0..image_data_size)
+        .into_par_iter()
+        .rev()
+        .map(|idx| {
+            let x = idx as u32 % image_width;
+            let y = idx as u32 / image_width;
+            let mut rng = pixel_rng(seed, idx);
+            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+            // A pixel is rendered on one thread from start to finish
+            let nodes_visited_before = object::nodes_visited();
+            let rays_before = scene::rays_traced();
+
+            for _ in 0..samples_per_pixel {
+                let rand_num1 = rng.gen_range(0.0..1.0);
+                let rand_num2 = rng.gen_range(0.0..1.0);
+                let u = (x as f64 + rand_num1) / (image_width as f64 - 1.0);
+                let v = (y as f64 + rand_num2) / (image_height as f64 - 1.0);
+                let ray = camera.get_ray(u, v, &mut rng);
+                pixel_color += integrator.ray_color(&ray, scene, &mut rng);
+            }
+
+            nodes_visited.fetch_add(
+                (object::nodes_visited() - nodes_visited_before) as u64,
+                Ordering::Relaxed,
+            );
+            rays.fetch_add(
+                (scene::rays_traced() - rays_before) as u64,
+                Ordering::Relaxed,
+            );
+            pixel_color * (1.0 / samples_per_pixel as f64)
+        })
+        .collect();
+
+    let stats = RenderStats {
+        build_time: object::build_time(),
+        samples: image_data_size as u64 * samples_per_pixel as u64,
+        rays: rays.into_inner(),
+        nodes_visited: nodes_visited.into_inner(),
+        render_time: start.elapsed(),
+    };
+    (image_data, stats)
+}
+
+/// Writes the linear image data out in the given format, or the one implied by the file's
+/// extension if none was given.
+fn write_image(
+    output_file: &str,
+    format: Option<&str>,
+    image_width: u32,
+    image_height: u32,
+    image_data: Vec<Color>,
+) -> IoResult<()> {
+    let extension = Path::new(output_file)
+        .extension()
+        .and_then(|ext| ext.to_str());
+    let image: Box<dyn Image> = match format.or(extension) {
+        Some("exr") => Box::new(EXR {
+            image_width,
+            image_height,
+            image_data,
+        }),
+        Some("hdr") => Box::new(HDR {
+            image_width,
+            image_height,
+            image_data,
+        }),
+        Some("pfm") => Box::new(PFM {
+            image_width,
+            image_height,
+            image_data,
+        }),
+        Some("png") => Box::new(PNG {
+            image_width,
+            image_height,
+            image_data,
+        }),
+        other => Box::new(PPM {
+            image_width,
+            image_height,
+            image_data,
+            format: match other {
+                Some("p6") => PPMFormat::Binary,
+                Some("p6-16") => PPMFormat::Binary16,
+                _ => PPMFormat::Ascii,
+            },
+        }),
+    };
+    image.render_to_file(output_file)
+}
+
+fn random_scene_camera(aspect_ratio: f64) -> Camera {
+    let look_from = Vec3::new(13.0, 2.0, 3.0);
+    let look_at = Vec3::new(0.0, 0.0, 0.0);
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.1;
+
+    Camera::new(
+        &look_from,
+        &look_at,
+        &vup,
+        20.0,
+        aspect_ratio,
+        aperture,
+        dist_to_focus,
+    )
+}
+
+fn run() -> IoResult<()> {
     // Image
     let yaml = load_yaml!("cli.yml");
     let matches = App::from(yaml).get_matches();
@@ -164,70 +271,142 @@ fn main() -> IoResult<()> {
     let image_height = (image_width as f64 / aspect_ratio) as u32;
     let samples_per_pixel = matches.value_of_t::<u32>("samples").unwrap_or(500);
     let max_depth = matches.value_of_t::<u32>("depth").unwrap_or(200).max(500);
+    let seed = match matches.value_of("seed") {
+        Some(_) => matches
+            .value_of_t::<u64>("seed")
+            .unwrap_or_else(|err| err.exit()),
+        None => {
+            // Report the seed picked, so that a render worth keeping can be made again
+            let seed = rand::thread_rng().gen();
+            eprintln!("seed: {}", seed);
+            seed
+        }
+    };
     let output_file = matches
         .value_of_os("OUTPUT")
-        .map(|f| f.to_str())
-        .flatten()
-        .ok_or(IoError::new(
-            IoErrorKind::InvalidInput,
-            "Must provide output file".to_string(),
-        ))?;
-
-    // Camera
-    let look_from = Vec3::new(13.0, 2.0, 3.0);
-    let look_at = Vec3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
+        .and_then(|f| f.to_str())
+        .ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::InvalidInput,
+                "Must provide output file".to_string(),
+            )
+        })?;
 
-    let camera = Arc::new(Camera::new(
-        &look_from,
-        &look_at,
-        &vup,
-        20.0,
-        aspect_ratio,
-        aperture,
-        dist_to_focus,
-    ));
+    // Camera and scene
+    let (camera, mut scene) = match matches.value_of("scene") {
+        Some(scene_file) => load_scene(scene_file, aspect_ratio)?,
+        None => (
+            random_scene_camera(aspect_ratio),
+            random_scene(&mut StdRng::seed_from_u64(seed)),
+        ),
+    };
 
-    // Scene
-    let scene = random_scene();
+    if let Some(environment_file) = matches.value_of("environment") {
+        let rotation = match matches.value_of("environment-rotation") {
+            Some(_) => matches
+                .value_of_t::<f64>("environment-rotation")
+                .unwrap_or_else(|err| err.exit()),
+            None => 0.0,
+        };
+        let intensity = match matches.value_of("environment-intensity") {
+            Some(_) => matches
+                .value_of_t::<f64>("environment-intensity")
+                .unwrap_or_else(|err| err.exit()),
+            None => 1.0,
+        };
+        scene.background = Box::new(Environment::load(
+            Path::new(environment_file),
+            rotation,
+            intensity,
+        )?);
+    }
 
-    let image_data_size = (image_width * image_height) as usize;
+    if matches.is_present("fog") {
+        let density = matches
+            .value_of_t::<f64>("fog")
+            .unwrap_or_else(|err| err.exit());
+        let extent = match matches.value_of("fog-extent") {
+            Some(_) => matches
+                .value_of_t::<f64>("fog-extent")
+                .unwrap_or_else(|err| err.exit()),
+            None => DEFAULT_FOG_EXTENT,
+        };
+        scene.fog = if density > 0.0 {
+            Some(Fog::new(density, Color::new(1.0, 1.0, 1.0), extent))
+        } else {
+            None
+        };
+    }
 
-    let image_data: Vec<Color> = (0..image_data_size)
-        .into_par_iter()
-        .rev()
-        .map(|idx| {
-            let x = idx as u32 % image_width;
-            let y = idx as u32 / image_width;
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+    let max_distance = match matches.value_of("max-distance") {
+        Some(_) => matches
+            .value_of_t::<f64>("max-distance")
+            .unwrap_or_else(|err| err.exit()),
+        None => 2.0 * camera.focus_dist,
+    };
+    let integrator: Box<dyn Integrator + Sync> = match matches.value_of("integrator") {
+        Some("naive") => Box::new(PathTracer::naive(max_depth)),
+        Some("ao") => Box::new(AmbientOcclusion::new(max_distance)),
+        Some("normals") => Box::new(Normals),
+        Some("depth") => Box::new(Depth::new(max_distance)),
+        Some("albedo") => Box::new(Albedo),
+        Some("bvh-cost") => Box::new(TraversalCost::new(100)),
+        _ => Box::new(PathTracer::new(max_depth)),
+    };
 
-            for _ in 0..samples_per_pixel {
-                let mut thread_rng = rand::thread_rng();
-                let rand_num1 = thread_rng.gen_range(0.0..1.0);
-                let rand_num2 = thread_rng.gen_range(0.0..1.0);
-                let u = (x as f64 + rand_num1) / (image_width as f64 - 1.0);
-                let v = (y as f64 + rand_num2) / (image_height as f64 - 1.0);
-                let ray = camera.get_ray(u, v);
-                pixel_color += ray_color(&ray, &scene, max_depth);
-            }
+    if matches.is_present("dump-scene") {
+        let scene_file = Path::new(output_file).with_extension("yml");
+        dump_scene_to_file(&camera, &scene, &scene_file.to_string_lossy())?;
+    }
 
-            // TODO: Fix this bit up
-            let scale = 1.0 / samples_per_pixel as f64;
-            pixel_color.red = (scale * pixel_color.red).sqrt().clamp(0.0, 0.9999999);
-            pixel_color.green = (scale * pixel_color.green).sqrt().clamp(0.0, 0.9999999);
-            pixel_color.blue = (scale * pixel_color.blue).sqrt().clamp(0.0, 0.9999999);
-            pixel_color
-        })
-        .collect();
+    let (image_data, stats) = render(
+        &camera,
+        &scene,
+        image_width,
+        image_height,
+        samples_per_pixel,
+        integrator.as_ref(),
+        seed,
+    );
+    if matches.is_present("stats") {
+        eprintln!("{}", stats);
+    }
 
-    let ppm_image = PPM {
+    write_image(
+        output_file,
+        matches.value_of("format"),
         image_width,
         image_height,
         image_data,
-    };
-    ppm_image.render_to_file(output_file)?;
+    )?;
 
     Ok(())
 }
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_is_deterministic() {
+        let camera = random_scene_camera(1.5);
+        let scene = random_scene(&mut StdRng::seed_from_u64(1));
+        let (image, _) = render(&camera, &scene, 30, 20, 4, &PathTracer::new(10), 1);
+        let other_scene = random_scene(&mut StdRng::seed_from_u64(1));
+        assert_eq!(
+            image,
+            render(&camera, &other_scene, 30, 20, 4, &PathTracer::new(10), 1).0
+        );
+        assert_ne!(
+            image,
+            render(&camera, &scene, 30, 20, 4, &PathTracer::new(10), 2).0
+        );
+    }
+}
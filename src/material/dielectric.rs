@@ -2,8 +2,11 @@ use crate::color::Color;
 use crate::material::Material;
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::scene::{hash, real, string, SceneWriter};
 use crate::vec3::Vec3;
-use rand::Rng;
+use rand::{Rng, RngCore};
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
 
 pub struct Dielectric {
     refraction_index: f64,
@@ -28,6 +31,7 @@ impl Material for Dielectric {
         hit_record: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        rng: &mut dyn RngCore,
     ) -> bool {
         attenuation.red = 1.0;
         attenuation.green = 1.0;
@@ -40,11 +44,11 @@ impl Material for Dielectric {
 
         let unit_direction = Vec3::unit_vector(&r_in.direction);
 
-        let cos_theta = Vec3::dot(&-unit_direction.clone(), &hit_record.normal).min(1.0);
+        let cos_theta = Vec3::dot(&-unit_direction, &hit_record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let rand_num = rand::thread_rng().gen_range(0.0..1.0);
+        let rand_num = rng.gen_range(0.0..1.0);
         let direction =
             if cannot_refract || Dielectric::reflectance(cos_theta, refraction_ratio) > rand_num {
                 Vec3::reflect(&unit_direction, &hit_record.normal)
@@ -52,9 +56,14 @@ impl Material for Dielectric {
                 Vec3::refract(&unit_direction, &hit_record.normal, refraction_ratio)
             };
 
-        let new_scattered = Ray::new(hit_record.p.clone(), direction);
-        scattered.origin = new_scattered.origin;
-        scattered.direction = new_scattered.direction;
+        *scattered = Ray::new(hit_record.p, direction, r_in.time);
         true
     }
+
+    fn to_yaml(&self, _writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("dielectric")),
+            ("refraction_index", real(self.refraction_index)),
+        ]))
+    }
 }
@@ -0,0 +1,48 @@
+use crate::color::Color;
+use crate::material::Material;
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::scene::{color, hash, string, SceneWriter};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+/// Emits light equally in every direction and absorbs everything that hits it.
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+        _attenuation: &mut Color,
+        _scattered: &mut Ray,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Vec3) -> Color {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    fn to_yaml(&self, _writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("diffuse_light")),
+            ("emit", color(&self.emit)),
+        ]))
+    }
+}
@@ -0,0 +1,58 @@
+use crate::color::Color;
+use crate::material::Material;
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::scene::{hash, string, SceneWriter};
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::f64::consts::PI;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// The phase function of smoke and fog: scatters equally in every direction, regardless of
+/// where the ray came from.
+pub struct Isotropic {
+    albedo: Arc<dyn Texture + Send + Sync>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Isotropic {
+        Isotropic::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture + Send + Sync>) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        *scattered = Ray::new(hit_record.p, Vec3::random_unit_vector(rng), r_in.time);
+        *attenuation = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
+        true
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn eval(&self, _r_in: &Ray, hit_record: &HitRecord, _scattered: &Ray) -> Color {
+        self.albedo.value(hit_record.u, hit_record.v, &hit_record.p) * (1.0 / (4.0 * PI))
+    }
+
+    fn to_yaml(&self, _writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("isotropic")),
+            ("albedo", self.albedo.to_yaml()?),
+        ]))
+    }
+}
@@ -2,14 +2,24 @@ use crate::color::Color;
 use crate::material::Material;
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::scene::{hash, string, SceneWriter};
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture + Send + Sync>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Lambertian {
+        Lambertian::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture + Send + Sync>) -> Lambertian {
         Lambertian { albedo }
     }
 }
@@ -17,28 +27,43 @@ impl Lambertian {
 impl Material for Lambertian {
     fn scatter(
         &self,
-        _ray_in: &Ray,
+        ray_in: &Ray,
         hit_record: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        rng: &mut dyn RngCore,
     ) -> bool {
-        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector();
+        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector(rng);
 
         // Catch degenerate scatter direction
         if scatter_direction.near_zero() {
             scatter_direction = hit_record.normal;
         }
 
-        let new_scattered = Ray::new(hit_record.p.clone(), scatter_direction);
-
-        // TODO: More succinct way of doing this?
-        scattered.origin = new_scattered.origin;
-        scattered.direction = new_scattered.direction;
+        *scattered = Ray::new(hit_record.p, scatter_direction, ray_in.time);
 
-        let new_attenuation = self.albedo;
+        let new_attenuation = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
         attenuation.red = new_attenuation.red;
         attenuation.green = new_attenuation.green;
         attenuation.blue = new_attenuation.blue;
-        return true;
+        true
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        // Scattering towards the normal plus a random unit vector is cosine weighted
+        let cosine = Vec3::dot(&hit_record.normal, &Vec3::unit_vector(&scattered.direction));
+        cosine.max(0.0) / std::f64::consts::PI
+    }
+
+    fn eval(&self, r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Color {
+        let albedo = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
+        albedo * self.scattering_pdf(r_in, hit_record, scattered)
+    }
+
+    fn to_yaml(&self, _writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("lambertian")),
+            ("albedo", self.albedo.to_yaml()?),
+        ]))
     }
 }
@@ -1,6 +1,11 @@
 use crate::color::Color;
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::scene::{unsupported, SceneWriter};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
 
 pub trait Material {
     fn scatter(
@@ -9,5 +14,71 @@ pub trait Material {
         hit_record: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        rng: &mut dyn RngCore,
     ) -> bool;
+    /// The density, per unit solid angle, with which `scatter` picks `scattered`, for materials
+    /// that scatter diffusely. Other materials can only be sampled by `scatter` itself, and
+    /// return 0.
+    fn scattering_pdf(&self, _r_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+    /// How much of the light arriving along `scattered` leaves back along `r_in`, cosine term
+    /// included, for materials with a `scattering_pdf`. Divided by that pdf, it's the
+    /// attenuation `scatter` gives for the directions it picks.
+    fn eval(&self, _r_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+    /// Light given off at the hit point, at texture coordinates `u`, `v`.
+    fn emitted(&self, _u: f64, _v: f64, _p: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+    /// Whether `emitted` gives off any light, making objects of this material worth aiming at.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+    fn to_yaml(&self, _writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Err(unsupported("material"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Dielectric, Isotropic, Lambertian, Metal};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_scattered_rays_keep_their_time() {
+        // Bounces have to happen at the same moment as the ray that hit, or reflections of
+        // moving objects lose their blur
+        let white = Color::new(1.0, 1.0, 1.0);
+        let materials: Vec<Arc<dyn Material + Send + Sync>> = vec![
+            Arc::new(Lambertian::new(white)),
+            Arc::new(Metal::new(white, 0.5)),
+            Arc::new(Dielectric::new(1.5)),
+            Arc::new(Isotropic::new(white)),
+        ];
+        let mut rng = StdRng::seed_from_u64(1);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.7);
+        for material in materials {
+            let hit_record = HitRecord {
+                normal: Vec3::new(0.0, 1.0, 0.0),
+                t: 1.0,
+                front_face: true,
+                ..HitRecord::new(material.clone())
+            };
+            let mut attenuation = Color::default();
+            let mut scattered = Ray::new(Vec3::origin(), Vec3::origin(), 0.0);
+            assert!(material.scatter(
+                &ray,
+                &hit_record,
+                &mut attenuation,
+                &mut scattered,
+                &mut rng
+            ));
+            assert_eq!(scattered.time, 0.7);
+        }
+    }
 }
@@ -2,15 +2,25 @@ use crate::color::Color;
 use crate::material::Material;
 use crate::object::HitRecord;
 use crate::ray::Ray;
+use crate::scene::{hash, real, string, SceneWriter};
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture + Send + Sync>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Metal {
+        Metal::textured(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture + Send + Sync>, fuzz: f64) -> Metal {
         Metal { albedo, fuzz }
     }
 }
@@ -22,17 +32,26 @@ impl Material for Metal {
         hit_record: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        rng: &mut dyn RngCore,
     ) -> bool {
         let reflected = Vec3::reflect(&Vec3::unit_vector(&r_in.direction), &hit_record.normal);
-        let new_scattered = Ray::new(
+        *scattered = Ray::new(
             hit_record.p,
-            reflected + self.fuzz * &Vec3::random_in_unit_sphere(),
+            reflected + self.fuzz * &Vec3::random_in_unit_sphere(rng),
+            r_in.time,
         );
-        scattered.direction = new_scattered.direction;
-        scattered.origin = new_scattered.origin;
-        attenuation.red = self.albedo.red;
-        attenuation.green = self.albedo.green;
-        attenuation.blue = self.albedo.blue;
+        let albedo = self.albedo.value(hit_record.u, hit_record.v, &hit_record.p);
+        attenuation.red = albedo.red;
+        attenuation.green = albedo.green;
+        attenuation.blue = albedo.blue;
         Vec3::dot(&scattered.direction, &hit_record.normal) > 0.0
     }
+
+    fn to_yaml(&self, _writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("metal")),
+            ("albedo", self.albedo.to_yaml()?),
+            ("fuzz", real(self.fuzz)),
+        ]))
+    }
 }
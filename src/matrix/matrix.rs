@@ -0,0 +1,213 @@
+use crate::vec3::Vec3;
+use std::ops::Mul;
+
+/// An affine transform: a 3x3 linear part in the first three columns and a translation in the
+/// fourth, with the implied bottom row of a 4x4 matrix always being [0, 0, 0, 1].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix {
+    pub rows: [[f64; 4]; 3],
+}
+
+impl Matrix {
+    pub fn new(rows: [[f64; 4]; 3]) -> Matrix {
+        Matrix { rows }
+    }
+
+    pub fn identity() -> Matrix {
+        Matrix::scaling(&Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: &Vec3) -> Matrix {
+        Matrix::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+        ])
+    }
+
+    pub fn scaling(scale: &Vec3) -> Matrix {
+        Matrix::new([
+            [scale.x, 0.0, 0.0, 0.0],
+            [0.0, scale.y, 0.0, 0.0],
+            [0.0, 0.0, scale.z, 0.0],
+        ])
+    }
+
+    /// A counter-clockwise rotation about the x axis, looking down it towards the origin.
+    pub fn rotation_x(degrees: f64) -> Matrix {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Matrix::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, -sin, 0.0],
+            [0.0, sin, cos, 0.0],
+        ])
+    }
+
+    pub fn rotation_y(degrees: f64) -> Matrix {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Matrix::new([
+            [cos, 0.0, sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin, 0.0, cos, 0.0],
+        ])
+    }
+
+    pub fn rotation_z(degrees: f64) -> Matrix {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Matrix::new([
+            [cos, -sin, 0.0, 0.0],
+            [sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ])
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        self.transform_vector(p) + Vec3::new(self.rows[0][3], self.rows[1][3], self.rows[2][3])
+    }
+
+    /// Transforms a direction, which unlike a point isn't affected by translation.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let [x, y, z] = self
+            .rows
+            .map(|row| row[0] * v.x + row[1] * v.y + row[2] * v.z);
+        Vec3::new(x, y, z)
+    }
+
+    /// The transposed linear part, without any translation. The transpose of a transform's
+    /// inverse is what carries its surface normals, keeping them perpendicular to the surface.
+    pub fn transpose(&self) -> Matrix {
+        let m = &self.rows;
+        Matrix::new([
+            [m[0][0], m[1][0], m[2][0], 0.0],
+            [m[0][1], m[1][1], m[2][1], 0.0],
+            [m[0][2], m[1][2], m[2][2], 0.0],
+        ])
+    }
+
+    /// How much the transform scales volumes by, negative if it turns them inside out.
+    pub fn determinant(&self) -> f64 {
+        let m = &self.rows;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// The transform that undoes this one, if there is one: nothing can undo squashing
+    /// everything flat.
+    pub fn inverse(&self) -> Option<Matrix> {
+        let m = &self.rows;
+        // Cofactors of the linear part, transposed
+        let adjugate = [
+            [
+                m[1][1] * m[2][2] - m[1][2] * m[2][1],
+                m[0][2] * m[2][1] - m[0][1] * m[2][2],
+                m[0][1] * m[1][2] - m[0][2] * m[1][1],
+            ],
+            [
+                m[1][2] * m[2][0] - m[1][0] * m[2][2],
+                m[0][0] * m[2][2] - m[0][2] * m[2][0],
+                m[0][2] * m[1][0] - m[0][0] * m[1][2],
+            ],
+            [
+                m[1][0] * m[2][1] - m[1][1] * m[2][0],
+                m[0][1] * m[2][0] - m[0][0] * m[2][1],
+                m[0][0] * m[1][1] - m[0][1] * m[1][0],
+            ],
+        ];
+        let determinant =
+            m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        let mut inverse = Matrix::new([[0.0; 4]; 3]);
+        for (row, adjugate_row) in inverse.rows.iter_mut().zip(adjugate.iter()) {
+            for (value, cofactor) in row.iter_mut().zip(adjugate_row.iter()) {
+                *value = cofactor / determinant;
+            }
+        }
+        // Then undo the translation, in the untranslated space
+        let translation = Vec3::new(m[0][3], m[1][3], m[2][3]);
+        let offset = -inverse.transform_vector(&translation);
+        for (row, value) in inverse.rows.iter_mut().zip([offset.x, offset.y, offset.z]) {
+            row[3] = value;
+        }
+        Some(inverse)
+    }
+}
+
+/// Composes two transforms, so that `a * b` applies `b` first and then `a`.
+impl Mul for Matrix {
+    type Output = Matrix;
+    fn mul(self, rhs: Matrix) -> Self::Output {
+        let rhs_rows = [rhs.rows[0], rhs.rows[1], rhs.rows[2], [0.0, 0.0, 0.0, 1.0]];
+        let mut product = Matrix::new([[0.0; 4]; 3]);
+        for (i, row) in product.rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * rhs_rows[k][j]).sum();
+            }
+        }
+        product
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nearly_equal(a: &Vec3, b: &Vec3) -> bool {
+        (*a - *b).len() < 1e-9
+    }
+
+    #[test]
+    fn test_transform() {
+        let p = Vec3::new(1.0, 2.0, 3.0);
+        let rotate = Matrix::rotation_z(90.0);
+        assert!(nearly_equal(
+            &rotate.transform_point(&p),
+            &Vec3::new(-2.0, 1.0, 3.0)
+        ));
+        assert!(nearly_equal(
+            &Matrix::rotation_x(90.0).transform_point(&p),
+            &Vec3::new(1.0, -3.0, 2.0)
+        ));
+        assert!(nearly_equal(
+            &Matrix::rotation_y(90.0).transform_point(&p),
+            &Vec3::new(3.0, 2.0, -1.0)
+        ));
+
+        let translate = Matrix::translation(&Vec3::new(0.0, 0.0, -10.0));
+        let both = translate * rotate;
+        assert!(nearly_equal(
+            &both.transform_point(&p),
+            &Vec3::new(-2.0, 1.0, -7.0)
+        ));
+        assert!(nearly_equal(
+            &both.transform_vector(&p),
+            &Vec3::new(-2.0, 1.0, 3.0)
+        ));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let transform = Matrix::translation(&Vec3::new(1.0, -2.0, 3.0))
+            * Matrix::rotation_y(30.0)
+            * Matrix::rotation_x(-70.0)
+            * Matrix::scaling(&Vec3::new(2.0, 0.5, -3.0));
+        let inverse = transform.inverse().unwrap();
+        let p = Vec3::new(0.3, 5.0, -1.0);
+        assert!(nearly_equal(
+            &inverse.transform_point(&transform.transform_point(&p)),
+            &p
+        ));
+        let identity = inverse * transform;
+        for (row, expected) in identity.rows.iter().zip(Matrix::identity().rows.iter()) {
+            for (value, expected) in row.iter().zip(expected.iter()) {
+                assert!((value - expected).abs() < 1e-9);
+            }
+        }
+
+        let flat = Matrix::scaling(&Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(flat.inverse(), None);
+    }
+}
@@ -0,0 +1,4 @@
+mod matrix;
+pub use matrix::Matrix;
+mod transform;
+pub use transform::Transform;
@@ -0,0 +1,54 @@
+use crate::matrix::Matrix;
+use crate::vec3::Vec3;
+
+/// Scaling, then rotation about the x, y and z axes in turn (in degrees), then translation.
+pub struct Transform {
+    pub scale: Vec3,
+    pub rotate: Vec3,
+    pub translate: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform {
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            rotate: Vec3::origin(),
+            translate: Vec3::origin(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Matrix {
+        Matrix::translation(&self.translate)
+            * Matrix::rotation_z(self.rotate.z)
+            * Matrix::rotation_y(self.rotate.y)
+            * Matrix::rotation_x(self.rotate.x)
+            * Matrix::scaling(&self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix() {
+        let transform = Transform {
+            scale: Vec3::new(1.0, 4.0, 1.0),
+            rotate: Vec3::new(0.0, 0.0, 90.0),
+            translate: Vec3::new(5.0, 0.0, 0.0),
+        };
+        let matrix = transform.matrix();
+        let point = matrix.transform_point(&Vec3::new(1.0, 1.0, 0.0));
+        assert!((point - Vec3::new(1.0, 1.0, 0.0)).len() < 1e-9);
+
+        // A normal on a slope flattens out as it's stretched upwards, then turns with it.
+        let normal_matrix = matrix.inverse().unwrap().transpose();
+        let normal = Vec3::unit_vector(
+            &normal_matrix.transform_vector(&Vec3::unit_vector(&Vec3::new(1.0, 1.0, 0.0))),
+        );
+        let expected = Vec3::unit_vector(&Vec3::new(-0.25, 1.0, 0.0));
+        assert!((normal - expected).len() < 1e-9);
+    }
+}
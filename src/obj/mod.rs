@@ -0,0 +1,3 @@
+mod mtl;
+mod obj;
+pub use obj::load_obj;
@@ -0,0 +1,184 @@
+use crate::color::Color;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::path::Path;
+use std::sync::Arc;
+
+pub type MaterialLibrary = HashMap<String, Arc<dyn Material + Send + Sync>>;
+
+/// Loads the materials defined by the MTL file at `path`.
+pub fn load_mtl(path: &Path) -> IoResult<MaterialLibrary> {
+    let source = fs::read_to_string(path)
+        .map_err(|err| IoError::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+    parse_mtl(&source)
+        .map_err(|err| IoError::new(err.kind(), format!("{}: {}", path.display(), err)))
+}
+
+/// Parses an MTL file, approximating each material with the closest one we have: see
+/// `MtlMaterial::build`. Statements we have no use for, such as texture maps, are ignored.
+pub fn parse_mtl(source: &str) -> IoResult<MaterialLibrary> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (idx, line) in source.lines().enumerate() {
+        let invalid = |message: String| {
+            IoError::new(
+                IoErrorKind::InvalidData,
+                format!("line {}: {}", idx + 1, message),
+            )
+        };
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(invalid("newmtl: expected a material name".to_string()));
+            }
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material.build());
+            }
+            current = Some((args.join(" "), MtlMaterial::default()));
+            continue;
+        }
+
+        let material = match (&mut current, keyword) {
+            (Some((_, material)), "Kd" | "Ks" | "Ns" | "Ni" | "d" | "Tr") => material,
+            (None, "Kd" | "Ks" | "Ns" | "Ni" | "d" | "Tr") => {
+                return Err(invalid(format!("{} before any newmtl", keyword)))
+            }
+            _ => continue,
+        };
+        match keyword {
+            "Kd" => material.diffuse = parse_color(keyword, &args).map_err(invalid)?,
+            "Ks" => material.specular = parse_color(keyword, &args).map_err(invalid)?,
+            "Ns" => material.shininess = parse_number(keyword, &args).map_err(invalid)?,
+            "Ni" => material.refraction_index = parse_number(keyword, &args).map_err(invalid)?,
+            "d" => material.opacity = parse_number(keyword, &args).map_err(invalid)?,
+            _ => material.opacity = 1.0 - parse_number(keyword, &args).map_err(invalid)?,
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material.build());
+    }
+    Ok(materials)
+}
+
+struct MtlMaterial {
+    diffuse: Color,
+    specular: Color,
+    shininess: f64,
+    refraction_index: f64,
+    opacity: f64,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> MtlMaterial {
+        MtlMaterial {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            refraction_index: 1.5,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Anything see-through becomes glass, anything more specular than diffuse becomes a metal
+    /// (the sharper its highlight, the less fuzzy) and everything else is Lambertian.
+    fn build(&self) -> Arc<dyn Material + Send + Sync> {
+        let brightest = |color: &Color| color.red.max(color.green).max(color.blue);
+        if self.opacity < 1.0 {
+            Arc::new(Dielectric::new(self.refraction_index))
+        } else if brightest(&self.specular) > brightest(&self.diffuse) {
+            let fuzz = (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt();
+            Arc::new(Metal::new(self.specular, fuzz))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+fn parse_number(keyword: &str, args: &[&str]) -> Result<f64, String> {
+    match args {
+        [value] => value
+            .parse()
+            .map_err(|_| format!("{}: malformed number \"{}\"", keyword, value)),
+        _ => Err(format!("{}: expected a single number", keyword)),
+    }
+}
+
+fn parse_color(keyword: &str, args: &[&str]) -> Result<Color, String> {
+    let values = args
+        .iter()
+        .map(|value| parse_number(keyword, &[value]))
+        .collect::<Result<Vec<f64>, String>>()?;
+    match values[..] {
+        // A single value is shorthand for a shade of grey
+        [value] => Ok(Color::new(value, value, value)),
+        [red, green, blue] => Ok(Color::new(red, green, blue)),
+        _ => Err(format!("{}: expected an RGB color", keyword)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::SceneWriter;
+    use yaml_rust::Yaml;
+
+    fn material_type(library: &MaterialLibrary, name: &str) -> Yaml {
+        let yaml = library[name].to_yaml(&mut SceneWriter::new()).unwrap();
+        yaml["type"].clone()
+    }
+
+    #[test]
+    fn test_parse_mtl() {
+        let library = parse_mtl(
+            "# Exported by hand
+newmtl clay
+Kd 0.6 0.3 0.2
+Ks 0.1
+
+newmtl chrome
+Kd 0 0 0
+Ks 0.9 0.9 0.9
+Ns 200
+illum 3
+
+newmtl glass
+Ni 1.45
+d 0.1
+",
+        )
+        .unwrap();
+        assert_eq!(library.len(), 3);
+        assert_eq!(material_type(&library, "clay").as_str(), Some("lambertian"));
+        assert_eq!(material_type(&library, "chrome").as_str(), Some("metal"));
+        assert_eq!(
+            material_type(&library, "glass").as_str(),
+            Some("dielectric")
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| parse_mtl(source).err().unwrap().to_string();
+        assert_eq!(
+            error("newmtl a\nKd 0.5 zero 0.5\n"),
+            "line 2: Kd: malformed number \"zero\""
+        );
+        assert_eq!(error("Kd 0.5 0.5 0.5\n"), "line 1: Kd before any newmtl");
+        assert_eq!(
+            error("newmtl a\n\nNs 1 2\n"),
+            "line 3: Ns: expected a single number"
+        );
+    }
+}
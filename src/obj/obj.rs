@@ -0,0 +1,382 @@
+use crate::color::Color;
+use crate::material::{Lambertian, Material};
+use crate::matrix::Matrix;
+use crate::obj::mtl::{load_mtl, MaterialLibrary};
+use crate::object::{MeshData, Object, ObjectList, TriangleMesh};
+use crate::vec3::Vec3;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Loads a Wavefront OBJ model as one triangle mesh per material, with `transform` (which must
+/// be invertible) baked into its vertices. Materials come from the model's MTL files unless
+/// `material` is given, in which case it's used for everything.
+pub fn load_obj(
+    path: &Path,
+    transform: &Matrix,
+    material: Option<Arc<dyn Material + Send + Sync>>,
+) -> IoResult<ObjectList> {
+    let source = fs::read_to_string(path)
+        .map_err(|err| IoError::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_obj(&source, dir, transform, material)
+        .map_err(|err| IoError::new(err.kind(), format!("{}: {}", path.display(), err)))
+}
+
+/// Parses an OBJ model, resolving material libraries relative to `dir`. See `load_obj`.
+pub fn parse_obj(
+    source: &str,
+    dir: &Path,
+    transform: &Matrix,
+    material: Option<Arc<dyn Material + Send + Sync>>,
+) -> IoResult<ObjectList> {
+    let mut parser = ObjParser {
+        positions: vec![],
+        uvs: vec![],
+        normals: vec![],
+        library: HashMap::new(),
+        groups: vec![],
+        group_names: HashMap::new(),
+        current: None,
+    };
+
+    for (idx, line) in source.lines().enumerate() {
+        let invalid = |message: String| {
+            IoError::new(
+                IoErrorKind::InvalidData,
+                format!("line {}: {}", idx + 1, message),
+            )
+        };
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                // An optional fourth w component is only meaningful for rational curves
+                let xyz = parse_numbers(keyword, &args, 3, 4).map_err(invalid)?;
+                parser.positions.push(Vec3::new(xyz[0], xyz[1], xyz[2]));
+            }
+            "vt" => {
+                let uvw = parse_numbers(keyword, &args, 1, 3).map_err(invalid)?;
+                parser
+                    .uvs
+                    .push((uvw[0], uvw.get(1).copied().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let xyz = parse_numbers(keyword, &args, 3, 3).map_err(invalid)?;
+                parser.normals.push(Vec3::new(xyz[0], xyz[1], xyz[2]));
+            }
+            "f" => parser.parse_face(&args).map_err(invalid)?,
+            "usemtl" => {
+                if args.is_empty() {
+                    return Err(invalid("usemtl: expected a material name".to_string()));
+                }
+                // Everything shares the one material when it's been overridden
+                let name = args.join(" ");
+                if material.is_none() {
+                    if !parser.library.contains_key(&name) {
+                        return Err(invalid(format!("unknown material \"{}\"", name)));
+                    }
+                    parser.use_material(name);
+                }
+            }
+            "mtllib" if material.is_none() => {
+                if args.is_empty() {
+                    return Err(invalid("mtllib: expected a file name".to_string()));
+                }
+                for file in args {
+                    let library =
+                        load_mtl(&dir.join(file)).map_err(|err| invalid(err.to_string()))?;
+                    parser.library.extend(library);
+                }
+            }
+            // Groups, smoothing groups, curves and so on don't affect how we render the model
+            _ => {}
+        }
+    }
+
+    let default_material: Arc<dyn Material + Send + Sync> =
+        Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    let normal_matrix = transform
+        .inverse()
+        .ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::InvalidInput,
+                "the transform flattens the model".to_string(),
+            )
+        })?
+        .transpose();
+    let mut meshes: Vec<Arc<dyn Object + Send + Sync>> = vec![];
+    for group in parser.groups.iter().filter(|group| !group.faces.is_empty()) {
+        let material = match (&material, &group.material) {
+            (Some(material), _) => material.clone(),
+            (None, Some(name)) => parser.library[name].clone(),
+            (None, None) => default_material.clone(),
+        };
+        meshes.push(Arc::new(TriangleMesh::new(
+            Arc::new(parser.mesh_data(group, transform, &normal_matrix)),
+            group.faces.clone(),
+            material,
+        )));
+    }
+    if meshes.is_empty() {
+        return Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "model has no faces".to_string(),
+        ));
+    }
+    Ok(ObjectList::new(meshes))
+}
+
+/// A face corner: indices of its position, texture coordinates and normal.
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+/// The faces using one material, with their own copy of each distinct vertex they use.
+struct Group {
+    material: Option<String>,
+    vertices: Vec<VertexKey>,
+    vertex_indices: HashMap<VertexKey, usize>,
+    faces: Vec<[usize; 3]>,
+}
+
+struct ObjParser {
+    positions: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+    library: MaterialLibrary,
+    groups: Vec<Group>,
+    group_names: HashMap<Option<String>, usize>,
+    current: Option<usize>,
+}
+
+impl ObjParser {
+    /// Returns the index of the group for faces using `material`, creating it if needed.
+    fn group(&mut self, material: Option<String>) -> usize {
+        let groups = &mut self.groups;
+        *self.group_names.entry(material.clone()).or_insert_with(|| {
+            groups.push(Group::new(material));
+            groups.len() - 1
+        })
+    }
+
+    fn use_material(&mut self, name: String) {
+        self.current = Some(self.group(Some(name)));
+    }
+
+    /// Adds a polygon, split into a fan of triangles around its first vertex.
+    fn parse_face(&mut self, args: &[&str]) -> Result<(), String> {
+        if args.len() < 3 {
+            return Err("f: expected at least three vertices".to_string());
+        }
+        let keys = args
+            .iter()
+            .map(|vertex| self.parse_vertex(vertex))
+            .collect::<Result<Vec<VertexKey>, String>>()?;
+
+        let current = match self.current {
+            Some(current) => current,
+            None => self.group(None),
+        };
+        self.current = Some(current);
+        let group = &mut self.groups[current];
+        let corners: Vec<usize> = keys.into_iter().map(|key| group.vertex(key)).collect();
+        for idx in 1..corners.len() - 1 {
+            group
+                .faces
+                .push([corners[0], corners[idx], corners[idx + 1]]);
+        }
+        Ok(())
+    }
+
+    /// Parses a face corner of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+    fn parse_vertex(&self, vertex: &str) -> Result<VertexKey, String> {
+        let parts: Vec<&str> = vertex.split('/').collect();
+        if parts.len() > 3 {
+            return Err(format!("f: malformed vertex \"{}\"", vertex));
+        }
+        let position = resolve_index(parts[0], self.positions.len(), "position")?;
+        let uv = match parts.get(1) {
+            Some(part) if !part.is_empty() => {
+                Some(resolve_index(part, self.uvs.len(), "texture coordinate")?)
+            }
+            _ => None,
+        };
+        let normal = match parts.get(2) {
+            Some(part) if !part.is_empty() => {
+                Some(resolve_index(part, self.normals.len(), "normal")?)
+            }
+            _ => None,
+        };
+        Ok((position, uv, normal))
+    }
+
+    /// Gathers the group's vertices, transformed. A mesh either has normals (or texture
+    /// coordinates) for every vertex or none at all, so they're dropped if any are missing.
+    fn mesh_data(&self, group: &Group, transform: &Matrix, normal_matrix: &Matrix) -> MeshData {
+        let positions = group
+            .vertices
+            .iter()
+            .map(|(position, _, _)| transform.transform_point(&self.positions[*position]))
+            .collect();
+        let uvs = group
+            .vertices
+            .iter()
+            .map(|(_, uv, _)| uv.map(|uv| self.uvs[uv]))
+            .collect::<Option<Vec<(f64, f64)>>>()
+            .unwrap_or_default();
+        let normals = group
+            .vertices
+            .iter()
+            .map(|(_, _, normal)| {
+                normal.map(|normal| {
+                    Vec3::unit_vector(&normal_matrix.transform_vector(&self.normals[normal]))
+                })
+            })
+            .collect::<Option<Vec<Vec3>>>()
+            .unwrap_or_default();
+        MeshData {
+            positions,
+            normals,
+            uvs,
+        }
+    }
+}
+
+impl Group {
+    fn new(material: Option<String>) -> Group {
+        Group {
+            material,
+            vertices: vec![],
+            vertex_indices: HashMap::new(),
+            faces: vec![],
+        }
+    }
+
+    fn vertex(&mut self, key: VertexKey) -> usize {
+        let vertices = &mut self.vertices;
+        *self.vertex_indices.entry(key).or_insert_with(|| {
+            vertices.push(key);
+            vertices.len() - 1
+        })
+    }
+}
+
+/// Converts a 1-based index, or a negative one counting back from the latest element, to a
+/// 0-based index.
+fn resolve_index(index: &str, len: usize, kind: &str) -> Result<usize, String> {
+    let value: i64 = index
+        .parse()
+        .map_err(|_| format!("f: malformed {} index \"{}\"", kind, index))?;
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        len as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!(
+            "f: {} index {} out of range ({} defined so far)",
+            kind, value, len
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_numbers(keyword: &str, args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(format!("{}: expected {} numbers", keyword, expected));
+    }
+    args.iter()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("{}: malformed number \"{}\"", keyword, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> IoResult<ObjectList> {
+        parse_obj(source, Path::new("."), &Matrix::identity(), None)
+    }
+
+    #[test]
+    fn test_parse_obj() {
+        // A unit cube made of quads, with its faces given in the different vertex formats
+        let cube = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 -1
+f 1/1/1 4/4/1 3/3/1 2/2/1
+f 5/1 6/2 7/3 8/4
+f 1//1 2//1 6//1 5//1
+f -5 -6 -2 -1 # 4 3 7 8
+f 2 3 7 6
+f 1 5 8 4
+";
+        let objects = parse(cube).unwrap();
+        assert_eq!(objects.objects.len(), 1);
+        let bounds = objects.bounding_box(0.0, 0.0).unwrap();
+        assert!((bounds.minimum - Vec3::new(0.0, 0.0, 0.0)).len() < 1e-3);
+        assert!((bounds.maximum - Vec3::new(1.0, 1.0, 1.0)).len() < 1e-3);
+
+        let transform = Matrix::translation(&Vec3::new(0.0, 1.0, 0.0))
+            * Matrix::rotation_y(90.0)
+            * Matrix::scaling(&Vec3::new(2.0, 2.0, 2.0));
+        let objects = parse_obj(cube, Path::new("."), &transform, None).unwrap();
+        let bounds = objects.bounding_box(0.0, 0.0).unwrap();
+        assert!((bounds.minimum - Vec3::new(0.0, 1.0, -2.0)).len() < 1e-3);
+        assert!((bounds.maximum - Vec3::new(2.0, 3.0, 0.0)).len() < 1e-3);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| parse(source).err().unwrap().to_string();
+        assert_eq!(error("v 0 0\n"), "line 1: v: expected 3 to 4 numbers");
+        assert_eq!(
+            error("v 0 0 0\nvn 0 x 1\n"),
+            "line 2: vn: malformed number \"x\""
+        );
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n"),
+            "line 5: f: position index 4 out of range (3 defined so far)"
+        );
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n"),
+            "line 4: f: texture coordinate index 1 out of range (0 defined so far)"
+        );
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            "line 3: f: expected at least three vertices"
+        );
+        assert_eq!(
+            error("usemtl shiny\n"),
+            "line 1: unknown material \"shiny\""
+        );
+        assert_eq!(error("v 0 0 0\n"), "model has no faces");
+    }
+}
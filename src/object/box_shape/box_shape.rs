@@ -0,0 +1,111 @@
+use crate::aabb::AABB;
+use crate::material::Material;
+use crate::object::{HitRecord, Object, ObjectList, Quad};
+use crate::ray::Ray;
+use crate::scene::{hash, string, vec3, SceneWriter};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// An axis-aligned box, made of six quads facing outwards.
+pub struct BoxShape {
+    minimum: Vec3,
+    maximum: Vec3,
+    material: Arc<dyn Material + Send + Sync>,
+    sides: ObjectList,
+}
+
+impl BoxShape {
+    /// A box with opposite corners at `a` and `b`.
+    pub fn new(a: Vec3, b: Vec3, material: Arc<dyn Material + Send + Sync>) -> BoxShape {
+        let minimum = Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let maximum = Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        let dx = Vec3::new(maximum.x - minimum.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, maximum.y - minimum.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, maximum.z - minimum.z);
+
+        let side = |corner: Vec3, u: Vec3, v: Vec3| -> Arc<dyn Object + Send + Sync> {
+            Arc::new(Quad::new(corner, u, v, material.clone()))
+        };
+        let sides = ObjectList::new(vec![
+            side(Vec3::new(minimum.x, minimum.y, maximum.z), dx, dy),
+            side(Vec3::new(maximum.x, minimum.y, maximum.z), -dz, dy),
+            side(Vec3::new(maximum.x, minimum.y, minimum.z), -dx, dy),
+            side(minimum, dz, dy),
+            side(Vec3::new(minimum.x, maximum.y, maximum.z), dx, -dz),
+            side(minimum, dx, dz),
+        ]);
+
+        BoxShape {
+            minimum,
+            maximum,
+            material,
+            sides,
+        }
+    }
+}
+
+impl Object for BoxShape {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        self.sides.hit(ray, t_min, t_max, rec, rng)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        Some(AABB::new(self.minimum, self.maximum).padded(1e-4))
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Object + Send + Sync>>) {
+        self.sides.collect_lights(lights);
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("box")),
+            ("min", vec3(&self.minimum)),
+            ("max", vec3(&self.maximum)),
+            ("material", writer.material(&self.material)?),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_sides_face_outwards() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let shape = BoxShape::new(
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(-1.0, -1.0, -1.0),
+            material.clone(),
+        );
+        let mut rec = HitRecord::new(material);
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        for axis in axes.iter().flat_map(|axis| [*axis, -*axis]) {
+            let ray = Ray::new(3.0 * &axis, -axis, 0.0);
+            assert!(shape.hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng));
+            assert_eq!(rec.t, 2.0);
+            assert_eq!(rec.normal, axis);
+            assert!(rec.front_face);
+        }
+    }
+}
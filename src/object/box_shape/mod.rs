@@ -0,0 +1,2 @@
+mod box_shape;
+pub use box_shape::BoxShape;
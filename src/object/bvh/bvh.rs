@@ -1,129 +1,618 @@
 use crate::aabb::AABB;
-use crate::object::{HitRecord, Object, ObjectList};
+use crate::object::{HitRecord, Object};
 use crate::ray::Ray;
-use rand::Rng;
+use crate::vec3::Vec3;
+use rand::RngCore;
+use rayon::prelude::*;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-pub struct BVHNode {
-    left: Arc<dyn Object + Send + Sync>,
-    right: Arc<dyn Object + Send + Sync>,
-    bounding_box: AABB,
+/// Nodes with this many objects or fewer are left whole when splitting them wouldn't pay off.
+const MAX_LEAF_SIZE: usize = 4;
+/// How many buckets objects are sorted into along each axis when looking for the best split.
+const BIN_COUNT: usize = 16;
+/// What visiting a node costs, relative to testing a ray against one object.
+const TRAVERSAL_COST: f64 = 1.0;
+/// Below this depth, nodes are split in half by count instead, so that no tree is deeper than
+/// the traversal stack.
+const MAX_SAH_DEPTH: usize = 32;
+const STACK_SIZE: usize = 64;
+/// Nodes with more objects than this have their two halves built on separate threads.
+const PARALLEL_BUILD_SIZE: usize = 1024;
+
+thread_local! {
+    static NODES_VISITED: Cell<usize> = const { Cell::new(0) };
+}
+
+static BUILD_TIME: AtomicU64 = AtomicU64::new(0);
+
+pub(super) fn add_nodes_visited(count: usize) {
+    NODES_VISITED.with(|visited| visited.set(visited.get() + count));
+}
+
+pub(super) fn add_build_time(elapsed: Duration) {
+    BUILD_TIME.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// How long building all the trees so far has taken.
+pub fn build_time() -> Duration {
+    Duration::from_nanos(BUILD_TIME.load(Ordering::Relaxed))
+}
+
+/// How many tree nodes rays traced on this thread have visited so far, for measuring how well
+/// the trees are built.
+pub fn nodes_visited() -> usize {
+    NODES_VISITED.with(Cell::get)
+}
+
+/// A bounding volume tree laid out flat, each node followed by its first child, with its
+/// second child and its objects found by offset.
+pub struct BVH {
+    pub(super) nodes: Vec<LinearNode>,
+    /// The objects, ordered so that each leaf's are next to each other
+    pub(super) objects: Vec<Arc<dyn Object + Send + Sync>>,
+    /// The box around everything, at full precision, or `None` if the tree is empty
+    pub(super) bounding_box: Option<AABB>,
+}
+
+/// A node packed into 32 bytes, so that two fit in a cache line. The box is stored in single
+/// precision, rounded outwards so that it never gets any smaller.
+#[repr(C, align(32))]
+pub(super) struct LinearNode {
+    pub(super) minimum: [f32; 3],
+    pub(super) maximum: [f32; 3],
+    /// The index of the first object for leaves, or of the second child for the others
+    pub(super) offset: u32,
+    /// How many objects a leaf holds, at most `MAX_LEAF_SIZE`, or 0 for nodes with children
+    pub(super) count: u16,
+    /// The axis the children were split along, for visiting the nearer one first
+    axis: u8,
+}
+
+impl LinearNode {
+    fn new(bounding_box: &AABB, offset: usize, count: usize, axis: usize) -> LinearNode {
+        let down = |x: f64| {
+            let y = x as f32;
+            if y as f64 > x {
+                y.next_down()
+            } else {
+                y
+            }
+        };
+        let up = |x: f64| {
+            let y = x as f32;
+            if (y as f64) < x {
+                y.next_up()
+            } else {
+                y
+            }
+        };
+        let (min, max) = (&bounding_box.minimum, &bounding_box.maximum);
+        LinearNode {
+            minimum: [down(min.x), down(min.y), down(min.z)],
+            maximum: [up(max.x), up(max.y), up(max.z)],
+            offset: offset as u32,
+            count: count as u16,
+            axis: axis as u8,
+        }
+    }
+
+    /// Whether the ray passes through the box between `t_min` and `t_max`, given one over each
+    /// of its direction's components.
+    fn hit(&self, origin: &Vec3, inv_direction: &[f64; 3], mut t_min: f64, mut t_max: f64) -> bool {
+        for a in 0..=2 {
+            let mut t0 = (self.minimum[a] as f64 - origin[a]) * inv_direction[a];
+            let mut t1 = (self.maximum[a] as f64 - origin[a]) * inv_direction[a];
+            if inv_direction[a] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
 }
 
-impl BVHNode {
-    pub fn new(
-        objects: &mut ObjectList,
+/// The tree as it's built, before being laid out flat.
+enum BuildNode {
+    /// A run of `count` objects starting at `start`
+    Leaf {
+        bounding_box: AABB,
         start: usize,
-        end: usize,
+        count: usize,
+    },
+    Split {
+        bounding_box: AABB,
+        axis: usize,
+        children: Box<(BuildNode, BuildNode)>,
+    },
+}
+
+impl BuildNode {
+    /// How many nodes there are in this part of the tree.
+    fn size(&self) -> usize {
+        match self {
+            BuildNode::Leaf { .. } => 1,
+            BuildNode::Split { children, .. } => 1 + children.0.size() + children.1.size(),
+        }
+    }
+
+    fn bounding_box(&self) -> AABB {
+        match self {
+            BuildNode::Leaf { bounding_box, .. } | BuildNode::Split { bounding_box, .. } => {
+                *bounding_box
+            }
+        }
+    }
+}
+
+/// An object along with the box around it and that box's centre, worked out once up front.
+struct Primitive {
+    object: Arc<dyn Object + Send + Sync>,
+    bounding_box: AABB,
+    centroid: Vec3,
+}
+
+/// A running total of the objects sorted into part of the node, and the box around them.
+#[derive(Clone, Copy)]
+struct Bin {
+    count: usize,
+    bounding_box: Option<AABB>,
+}
+
+impl Bin {
+    fn empty() -> Bin {
+        Bin {
+            count: 0,
+            bounding_box: None,
+        }
+    }
+
+    fn add(&mut self, count: usize, bounding_box: &Option<AABB>) {
+        self.count += count;
+        self.bounding_box = match (self.bounding_box, bounding_box) {
+            (Some(a), Some(b)) => Some(AABB::bounding_box(&a, b)),
+            (a, b) => a.or(*b),
+        };
+    }
+
+    /// The surface area heuristic's estimate of what testing a ray against this part costs,
+    /// scaled by the surface area of the whole node.
+    fn cost(&self) -> f64 {
+        self.bounding_box
+            .map_or(0.0, |bounding_box| bounding_box.surface_area())
+            * self.count as f64
+    }
+}
+
+impl BVH {
+    /// Builds a tree over the objects, which must all have bounding boxes, splitting each node
+    /// where the surface area heuristic expects rays to find their hits soonest. Large trees
+    /// are built on several threads.
+    pub fn new(objects: &[Arc<dyn Object + Send + Sync>], time0: f64, time1: f64) -> BVH {
+        BVH::with_parallel_size(objects, time0, time1, PARALLEL_BUILD_SIZE)
+    }
+
+    /// Builds the tree, splitting off the two halves of any node with more than `parallel_size`
+    /// objects to build at once.
+    fn with_parallel_size(
+        objects: &[Arc<dyn Object + Send + Sync>],
         time0: f64,
         time1: f64,
-    ) -> BVHNode {
-        let axis: usize = rand::thread_rng().gen_range(0..=2);
-        let comparator = if axis == 0 {
-            box_x_compare
-        } else if axis == 1 {
-            box_y_compare
-        } else {
-            box_z_compare
+        parallel_size: usize,
+    ) -> BVH {
+        let start = Instant::now();
+        let mut primitives: Vec<Primitive> = objects
+            .par_iter()
+            .map(|object| {
+                let bounding_box = object
+                    .bounding_box(time0, time1)
+                    .expect("No bounding box in bvh_node constructor.");
+                Primitive {
+                    object: object.clone(),
+                    bounding_box,
+                    centroid: bounding_box.centroid(),
+                }
+            })
+            .collect();
+        let mut nodes = vec![];
+        let mut bounding_box = None;
+        if !primitives.is_empty() {
+            let root = BVH::build(&mut primitives, 0, 0, parallel_size);
+            nodes.reserve(root.size());
+            BVH::flatten(&root, &mut nodes);
+            bounding_box = Some(root.bounding_box());
+        }
+        let tree = BVH {
+            nodes,
+            bounding_box,
+            objects: primitives
+                .into_iter()
+                .map(|primitive| primitive.object)
+                .collect(),
         };
+        add_build_time(start.elapsed());
+        tree
+    }
 
-        let object_span = end - start;
+    /// Builds the part of the tree over `primitives`, which start `start` objects in.
+    fn build(
+        primitives: &mut [Primitive],
+        start: usize,
+        depth: usize,
+        parallel_size: usize,
+    ) -> BuildNode {
+        let mut bounds = Bin::empty();
+        for primitive in primitives.iter() {
+            bounds.add(1, &Some(primitive.bounding_box));
+        }
+        let bounding_box = bounds
+            .bounding_box
+            .unwrap_or_else(|| AABB::new(Vec3::origin(), Vec3::origin()));
+        let leaf = BuildNode::Leaf {
+            bounding_box,
+            start,
+            count: primitives.len(),
+        };
 
-        let (left, right) = if object_span == 1 {
-            (
-                objects.objects[start].clone(),
-                objects.objects[start].clone(),
-            )
-        } else if object_span == 2 {
-            if comparator(&objects.objects[start], &objects.objects[start + 1])
-                == std::cmp::Ordering::Less
-            {
-                (
-                    objects.objects[start].clone(),
-                    objects.objects[start + 1].clone(),
-                )
-            } else {
-                (
-                    objects.objects[start + 1].clone(),
-                    objects.objects[start].clone(),
-                )
+        let best_split = if depth < MAX_SAH_DEPTH {
+            BVH::best_split(primitives, &bounding_box)
+        } else {
+            None
+        };
+        let (mid, axis) = match best_split {
+            Some((cost, axis, split, centroid_bounds)) => {
+                if primitives.len() <= MAX_LEAF_SIZE && cost >= primitives.len() as f64 {
+                    return leaf;
+                }
+                let mid = partition(primitives, |primitive| {
+                    bin_index(primitive, axis, &centroid_bounds) < split
+                });
+                (mid, axis)
             }
+            // Everything is centred on the same point, or the tree is already deep, so there's
+            // nothing to choose between
+            None if primitives.len() <= MAX_LEAF_SIZE => return leaf,
+            None => (primitives.len() / 2, 0),
+        };
+
+        let parallel = primitives.len() > parallel_size;
+        let (left, right) = primitives.split_at_mut(mid);
+        let children = if parallel {
+            rayon::join(
+                || BVH::build(left, start, depth + 1, parallel_size),
+                || BVH::build(right, start + mid, depth + 1, parallel_size),
+            )
         } else {
-            objects.objects[start..end].sort_by(comparator);
-
-            let mid = start + object_span / 2;
-            let a: Arc<dyn Object + Send + Sync> =
-                Arc::new(BVHNode::new(objects, start, mid, time0, time1));
-            let b: Arc<dyn Object + Send + Sync> =
-                Arc::new(BVHNode::new(objects, mid, end, time0, time1));
-            (a, b)
+            (
+                BVH::build(left, start, depth + 1, parallel_size),
+                BVH::build(right, start + mid, depth + 1, parallel_size),
+            )
         };
-        let box_left = left.bounding_box(time0, time1);
-        let box_right = right.bounding_box(time0, time1);
-
-        match (box_left, box_right) {
-            (Some(box_left), Some(box_right)) => {
-                let bounding_box = AABB::bounding_box(&box_left, &box_right);
-                BVHNode {
-                    left,
-                    right,
-                    bounding_box,
+        BuildNode::Split {
+            bounding_box,
+            axis,
+            children: Box::new(children),
+        }
+    }
+
+    /// Lays out the node and everything below it depth first, returning the node's index.
+    fn flatten(node: &BuildNode, nodes: &mut Vec<LinearNode>) -> usize {
+        let index = nodes.len();
+        match node {
+            BuildNode::Leaf {
+                bounding_box,
+                start,
+                count,
+            } => {
+                nodes.push(LinearNode::new(bounding_box, *start, *count, 0));
+            }
+            BuildNode::Split {
+                bounding_box,
+                axis,
+                children,
+            } => {
+                nodes.push(LinearNode::new(bounding_box, 0, 0, *axis));
+                BVH::flatten(&children.0, nodes);
+                let second = BVH::flatten(&children.1, nodes);
+                nodes[index].offset = second as u32;
+            }
+        }
+        index
+    }
+
+    /// Sorts the objects' centres into buckets along each axis and finds the boundary between
+    /// buckets that's cheapest to split at, returning its cost, the axis, the index of the
+    /// first bucket on the far side and the box around the centres the buckets span.
+    fn best_split(
+        primitives: &[Primitive],
+        bounding_box: &AABB,
+    ) -> Option<(f64, usize, usize, AABB)> {
+        let mut centroids = Bin::empty();
+        for primitive in primitives {
+            centroids.add(1, &Some(AABB::new(primitive.centroid, primitive.centroid)));
+        }
+        let centroid_bounds = centroids.bounding_box?;
+        let area = bounding_box.surface_area().max(f64::MIN_POSITIVE);
+
+        let mut best: Option<(f64, usize, usize, AABB)> = None;
+        for axis in 0..=2 {
+            if centroid_bounds.maximum[axis] <= centroid_bounds.minimum[axis] {
+                continue;
+            }
+            let mut bins = [Bin::empty(); BIN_COUNT];
+            for primitive in primitives {
+                bins[bin_index(primitive, axis, &centroid_bounds)]
+                    .add(1, &Some(primitive.bounding_box));
+            }
+
+            // Sweep in from the far end so that each boundary's far side is known
+            let mut far_sides = [Bin::empty(); BIN_COUNT];
+            let mut far = Bin::empty();
+            for split in (1..BIN_COUNT).rev() {
+                far.add(bins[split].count, &bins[split].bounding_box);
+                far_sides[split] = far;
+            }
+
+            let mut near = Bin::empty();
+            for split in 1..BIN_COUNT {
+                near.add(bins[split - 1].count, &bins[split - 1].bounding_box);
+                let far = far_sides[split];
+                if near.count == 0 || far.count == 0 {
+                    continue;
+                }
+                let cost = TRAVERSAL_COST + (near.cost() + far.cost()) / area;
+                if best.is_none_or(|(best_cost, ..)| cost < best_cost) {
+                    best = Some((cost, axis, split, centroid_bounds));
                 }
             }
-            (_, _) => panic!("No bounding box in bvh_node constructor.\n"),
+        }
+        best
+    }
+}
+
+fn bin_index(primitive: &Primitive, axis: usize, centroid_bounds: &AABB) -> usize {
+    let extent = centroid_bounds.maximum[axis] - centroid_bounds.minimum[axis];
+    let offset = (primitive.centroid[axis] - centroid_bounds.minimum[axis]) / extent;
+    ((offset * BIN_COUNT as f64) as usize).min(BIN_COUNT - 1)
+}
+
+/// Moves the primitives for which `near` holds to the front, returning how many there are.
+fn partition(primitives: &mut [Primitive], near: impl Fn(&Primitive) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..primitives.len() {
+        if near(&primitives[i]) {
+            primitives.swap(i, mid);
+            mid += 1;
         }
     }
+    mid
 }
 
-impl Object for BVHNode {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        if !self.bounding_box.hit(ray, t_min, t_max) {
+impl Object for BVH {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        if self.nodes.is_empty() {
             return false;
         }
+        let inv_direction = [
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        ];
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+        let mut visited = 0;
 
-        let hit_left = self.left.hit(ray, t_min, t_max, rec);
-        let hit_right = self
-            .right
-            .hit(ray, t_min, if hit_left { rec.t } else { t_max }, rec);
+        let mut stack = [0; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut index = 0;
+        loop {
+            visited += 1;
+            let node = &self.nodes[index];
+            if node.hit(&ray.origin, &inv_direction, t_min, closest_so_far) {
+                if node.count > 0 {
+                    let start = node.offset as usize;
+                    for object in &self.objects[start..start + node.count as usize] {
+                        if object.hit(ray, t_min, closest_so_far, rec, rng) {
+                            hit_anything = true;
+                            closest_so_far = rec.t;
+                        }
+                    }
+                } else {
+                    // Look at the child nearer the ray's origin first, saving the other
+                    let (near, far) = if inv_direction[node.axis as usize] < 0.0 {
+                        (node.offset as usize, index + 1)
+                    } else {
+                        (index + 1, node.offset as usize)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    index = near;
+                    continue;
+                }
+            }
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            index = stack[stack_len];
+        }
 
-        return hit_left || hit_right;
+        add_nodes_visited(visited);
+        hit_anything
     }
+
     fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
-        Some(self.bounding_box)
+        self.bounding_box
     }
 }
 
-fn box_compare<'r, 's>(
-    a: &'r Arc<dyn Object + Send + Sync>,
-    b: &'s Arc<dyn Object + Send + Sync>,
-    axis: usize,
-) -> std::cmp::Ordering {
-    let box_a = a.bounding_box(0.0, 0.0);
-    let box_b = b.bounding_box(0.0, 0.0);
-    match (box_a, box_b) {
-        (Some(box_a), Some(box_b)) => box_a.minimum[axis]
-            .partial_cmp(&box_b.minimum[axis])
-            .unwrap(),
-        (_, _) => panic!("No bounding box in bvh_node constructor.\n"),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::object::hit_t;
+    use crate::object::ObjectList;
+    use crate::object::Sphere;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_matches_object_list() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let mut objects: Vec<Arc<dyn Object + Send + Sync>> = vec![Arc::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            material.clone(),
+        ))];
+        for _ in 0..300 {
+            let center = Vec3::random(&mut rng, -10.0, 10.0);
+            let radius = rng.gen_range(0.05..1.0);
+            objects.push(Arc::new(Sphere::new(center, radius, material.clone())));
+        }
+        let tree = BVH::new(&objects, 0.0, 0.0);
+        let list = ObjectList::new(objects);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = Ray::new(
+                Vec3::random(&mut rng, -15.0, 15.0),
+                Vec3::random_unit_vector(&mut rng),
+                0.0,
+            );
+            let expected = hit_t(&list, &ray, &mut rng);
+            assert_eq!(hit_t(&tree, &ray, &mut rng), expected);
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 500);
     }
-}
 
-fn box_x_compare<'r, 's>(
-    a: &'r Arc<dyn Object + Send + Sync>,
-    b: &'s Arc<dyn Object + Send + Sync>,
-) -> std::cmp::Ordering {
-    box_compare(a, b, 0)
-}
+    #[test]
+    fn test_small_groups_share_a_leaf() {
+        // A few objects close together aren't worth splitting up, and a lone one isn't
+        // duplicated into two children
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let sphere = |x: f64| -> Arc<dyn Object + Send + Sync> {
+            Arc::new(Sphere::new(Vec3::new(x, 0.0, 0.0), 1.0, material.clone()))
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let ray = Ray::new(Vec3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        for objects in &[
+            vec![sphere(0.0)],
+            vec![sphere(0.0), sphere(0.5), sphere(1.0)],
+        ] {
+            let tree = BVH::new(objects, 0.0, 0.0);
+            let before = nodes_visited();
+            assert_eq!(hit_t(&tree, &ray, &mut rng), Some(9.0));
+            assert_eq!(nodes_visited() - before, 1);
+        }
 
-fn box_y_compare<'r, 's>(
-    a: &'r Arc<dyn Object + Send + Sync>,
-    b: &'s Arc<dyn Object + Send + Sync>,
-) -> std::cmp::Ordering {
-    box_compare(a, b, 1)
-}
-fn box_z_compare<'r, 's>(
-    a: &'r Arc<dyn Object + Send + Sync>,
-    b: &'s Arc<dyn Object + Send + Sync>,
-) -> std::cmp::Ordering {
-    box_compare(a, b, 2)
+        // Far apart, they're split so that a ray only visits the part it passes through
+        let tree = BVH::new(&[sphere(0.0), sphere(100.0)], 0.0, 0.0);
+        let before = nodes_visited();
+        let down = Ray::new(Vec3::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert_eq!(hit_t(&tree, &down, &mut rng), Some(9.0));
+        assert_eq!(nodes_visited() - before, 3);
+    }
+
+    #[test]
+    fn test_nearer_child_first() {
+        // Rays along a row of spheres find the nearest one just as quickly from either end
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let objects: Vec<Arc<dyn Object + Send + Sync>> = (0..64)
+            .map(|i| -> Arc<dyn Object + Send + Sync> {
+                Arc::new(Sphere::new(
+                    Vec3::new(i as f64 * 3.0, 0.0, 0.0),
+                    1.0,
+                    material.clone(),
+                ))
+            })
+            .collect();
+        let tree = BVH::new(&objects, 0.0, 0.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut cost = |origin: f64, direction: f64, expected: f64| {
+            let ray = Ray::new(
+                Vec3::new(origin, 0.0, 0.0),
+                Vec3::new(direction, 0.0, 0.0),
+                0.0,
+            );
+            let before = nodes_visited();
+            assert_eq!(hit_t(&tree, &ray, &mut rng), Some(expected));
+            nodes_visited() - before
+        };
+        let forwards = cost(-10.0, 1.0, 9.0);
+        assert_eq!(cost(199.0, -1.0, 9.0), forwards);
+        assert!(forwards < 20);
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(std::mem::size_of::<LinearNode>(), 32);
+        assert_eq!(std::mem::align_of::<LinearNode>(), 32);
+
+        // Boxes only ever grow when they're rounded to single precision
+        let bounding_box = AABB::new(Vec3::new(0.1, -0.1, 1e-9), Vec3::new(0.3, 1e10 + 1.0, 2.0));
+        let node = LinearNode::new(&bounding_box, 0, 0, 0);
+        for a in 0..=2 {
+            assert!((node.minimum[a] as f64) <= bounding_box.minimum[a]);
+            assert!((node.maximum[a] as f64) >= bounding_box.maximum[a]);
+        }
+
+        let empty = BVH::new(&[], 0.0, 0.0);
+        assert!(empty.bounding_box(0.0, 0.0).is_none());
+        let ray = Ray::new(Vec3::origin(), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(hit_t(&empty, &ray, &mut rng), None);
+    }
+
+    #[test]
+    fn test_parallel_build() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let objects: Vec<Arc<dyn Object + Send + Sync>> = (0..20000)
+            .map(|_| -> Arc<dyn Object + Send + Sync> {
+                let center = Vec3::random(&mut rng, -50.0, 50.0);
+                let radius = rng.gen_range(0.2..1.0);
+                Arc::new(Sphere::new(center, radius, material.clone()))
+            })
+            .collect();
+        let serial = BVH::with_parallel_size(&objects, 0.0, 0.0, usize::MAX);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let parallel = pool.install(|| BVH::with_parallel_size(&objects, 0.0, 0.0, 1));
+        assert_eq!(parallel.nodes.len(), serial.nodes.len());
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = Ray::new(
+                Vec3::random(&mut rng, -60.0, 60.0),
+                Vec3::random_unit_vector(&mut rng),
+                0.0,
+            );
+            let before = nodes_visited();
+            let expected = hit_t(&serial, &ray, &mut rng);
+            let serial_cost = nodes_visited() - before;
+            let before = nodes_visited();
+            assert_eq!(hit_t(&parallel, &ray, &mut rng), expected);
+            assert_eq!(nodes_visited() - before, serial_cost);
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 500);
+    }
 }
@@ -1,2 +1,8 @@
 mod bvh;
-pub use bvh::BVHNode;
+#[cfg(not(feature = "simd"))]
+pub use bvh::BVH;
+pub use bvh::{build_time, nodes_visited};
+#[cfg(feature = "simd")]
+mod wide_bvh;
+#[cfg(feature = "simd")]
+pub use wide_bvh::WideBVH as BVH;
@@ -0,0 +1,475 @@
+use super::bvh::{add_build_time, add_nodes_visited, LinearNode, BVH};
+use crate::aabb::AABB;
+use crate::object::{HitRecord, Object, Triangle4};
+use crate::ray::Ray;
+use rand::RngCore;
+use std::mem::MaybeUninit;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// How many children each node has, one per SIMD lane.
+const WIDTH: usize = 4;
+/// Every node visited leaves at most three more entries on the stack than it took, and the
+/// trees are no deeper than the binary ones they're collapsed from.
+const STACK_SIZE: usize = 3 * 64 + 1;
+/// How much to stretch the far end of each box test so that single precision rounding never
+/// makes a ray miss a box it grazes, as in PBRT.
+const FAR_SCALE: f32 = 1.0 + 2.0 * 3.0 * f32::EPSILON * 0.5 / (1.0 - 3.0 * f32::EPSILON * 0.5);
+
+/// A bounding volume tree with four children to a node, whose boxes a ray is tested against
+/// all at once. It's built by collapsing every other level of the binary tree, and leaves of
+/// triangles are packed so that they're tested all at once too.
+pub struct WideBVH {
+    nodes: Vec<WideNode>,
+    objects: Vec<Arc<dyn Object + Send + Sync>>,
+    bounding_box: Option<AABB>,
+}
+
+/// A node holding its children's boxes one axis at a time, so that each row loads straight
+/// into a SIMD register. Slots without a child have inside out boxes that nothing hits.
+#[repr(C, align(64))]
+struct WideNode {
+    minimum: [[f32; WIDTH]; 3],
+    maximum: [[f32; WIDTH]; 3],
+    /// The index of the first object for leaves, or of the child node for the others
+    offsets: [u32; WIDTH],
+    /// How many objects each leaf holds, or 0 for children that are nodes themselves
+    counts: [u16; WIDTH],
+}
+
+/// The parts of a ray the box tests need, in single precision.
+struct WideRay {
+    origin: [f32; 3],
+    inv_direction: [f32; 3],
+    negative: [bool; 3],
+}
+
+impl WideRay {
+    fn new(ray: &Ray) -> WideRay {
+        let inv_direction = [
+            (1.0 / ray.direction.x) as f32,
+            (1.0 / ray.direction.y) as f32,
+            (1.0 / ray.direction.z) as f32,
+        ];
+        WideRay {
+            origin: [
+                ray.origin.x as f32,
+                ray.origin.y as f32,
+                ray.origin.z as f32,
+            ],
+            inv_direction,
+            negative: [
+                inv_direction[0] < 0.0,
+                inv_direction[1] < 0.0,
+                inv_direction[2] < 0.0,
+            ],
+        }
+    }
+}
+
+impl WideNode {
+    fn empty() -> WideNode {
+        WideNode {
+            minimum: [[f32::INFINITY; WIDTH]; 3],
+            maximum: [[f32::NEG_INFINITY; WIDTH]; 3],
+            offsets: [0; WIDTH],
+            counts: [0; WIDTH],
+        }
+    }
+
+    /// Where the ray enters each child's box between `t_min` and `t_max`, or infinity for the
+    /// ones it misses.
+    #[cfg(target_arch = "x86_64")]
+    fn intersect(&self, ray: &WideRay, t_min: f32, t_max: f32) -> [f32; WIDTH] {
+        use std::arch::x86_64::*;
+
+        // SSE is part of every x86_64 processor, so these need no runtime check
+        unsafe {
+            let mut near = _mm_set1_ps(t_min);
+            let mut far = _mm_set1_ps(t_max);
+            for a in 0..=2 {
+                let (entry, exit) = if ray.negative[a] {
+                    (&self.maximum[a], &self.minimum[a])
+                } else {
+                    (&self.minimum[a], &self.maximum[a])
+                };
+                let origin = _mm_set1_ps(ray.origin[a]);
+                let inv_direction = _mm_set1_ps(ray.inv_direction[a]);
+                let t0 = _mm_mul_ps(
+                    _mm_sub_ps(_mm_load_ps(entry.as_ptr()), origin),
+                    inv_direction,
+                );
+                let t1 = _mm_mul_ps(
+                    _mm_sub_ps(_mm_load_ps(exit.as_ptr()), origin),
+                    inv_direction,
+                );
+                // These return their second argument when either is NaN, which happens when
+                // the ray runs along a face, so that such an axis doesn't limit anything
+                near = _mm_max_ps(t0, near);
+                far = _mm_min_ps(_mm_mul_ps(t1, _mm_set1_ps(FAR_SCALE)), far);
+            }
+            let hit = _mm_cmplt_ps(near, far);
+            let near = _mm_or_ps(
+                _mm_and_ps(hit, near),
+                _mm_andnot_ps(hit, _mm_set1_ps(f32::INFINITY)),
+            );
+            let mut result = [0.0; WIDTH];
+            _mm_storeu_ps(result.as_mut_ptr(), near);
+            result
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn intersect(&self, ray: &WideRay, t_min: f32, t_max: f32) -> [f32; WIDTH] {
+        self.intersect_scalar(ray, t_min, t_max)
+    }
+
+    /// The same as `intersect` a lane at a time, for processors without SSE.
+    #[cfg(any(not(target_arch = "x86_64"), test))]
+    fn intersect_scalar(&self, ray: &WideRay, t_min: f32, t_max: f32) -> [f32; WIDTH] {
+        let mut result = [f32::INFINITY; WIDTH];
+        for (i, result) in result.iter_mut().enumerate() {
+            let mut near = t_min;
+            let mut far = t_max;
+            for a in 0..=2 {
+                let (entry, exit) = if ray.negative[a] {
+                    (self.maximum[a][i], self.minimum[a][i])
+                } else {
+                    (self.minimum[a][i], self.maximum[a][i])
+                };
+                let t0 = (entry - ray.origin[a]) * ray.inv_direction[a];
+                let t1 = (exit - ray.origin[a]) * ray.inv_direction[a] * FAR_SCALE;
+                near = if t0 > near { t0 } else { near };
+                far = if t1 < far { t1 } else { far };
+            }
+            if near < far {
+                *result = near;
+            }
+        }
+        result
+    }
+}
+
+/// Rounds up to single precision, so that a box test never cuts a ray short.
+fn round_up(x: f64) -> f32 {
+    let y = x as f32;
+    if (y as f64) < x {
+        y.next_up()
+    } else {
+        y
+    }
+}
+
+fn surface_area(node: &LinearNode) -> f32 {
+    let [dx, dy, dz] = [0, 1, 2].map(|a| node.maximum[a] - node.minimum[a]);
+    dx * dy + dy * dz + dz * dx
+}
+
+impl WideBVH {
+    /// Builds a binary tree over the objects, which must all have bounding boxes, and gathers
+    /// its nodes up four at a time.
+    pub fn new(objects: &[Arc<dyn Object + Send + Sync>], time0: f64, time1: f64) -> WideBVH {
+        let binary = BVH::new(objects, time0, time1);
+        let start = Instant::now();
+        let mut nodes = vec![];
+        if !binary.nodes.is_empty() {
+            nodes.reserve(binary.nodes.len() / 2 + 1);
+            WideBVH::collapse(&binary.nodes, 0, &mut nodes);
+        }
+
+        let mut objects: Vec<Arc<dyn Object + Send + Sync>> =
+            Vec::with_capacity(binary.objects.len());
+        for node in &mut nodes {
+            for slot in 0..WIDTH {
+                let count = node.counts[slot] as usize;
+                if count == 0 {
+                    continue;
+                }
+                let start = node.offsets[slot] as usize;
+                let leaf = &binary.objects[start..start + count];
+                node.offsets[slot] = objects.len() as u32;
+                if count > 1 && leaf.iter().all(|object| object.triangle().is_some()) {
+                    objects.push(Arc::new(Triangle4::new(leaf)));
+                    node.counts[slot] = 1;
+                } else {
+                    objects.extend(leaf.iter().cloned());
+                }
+            }
+        }
+        add_build_time(start.elapsed());
+        WideBVH {
+            nodes,
+            objects,
+            bounding_box: binary.bounding_box,
+        }
+    }
+
+    /// Adds a node standing for the binary one at `index` to `nodes`, with the binary node's
+    /// descendants as children, and returns where it went.
+    fn collapse(binary: &[LinearNode], index: usize, nodes: &mut Vec<WideNode>) -> usize {
+        let mut children = if binary[index].count > 0 {
+            // Only a tree that's a single leaf has one at the top
+            vec![index]
+        } else {
+            vec![index + 1, binary[index].offset as usize]
+        };
+        // Open up the biggest child with children of its own until the node is full
+        while children.len() < WIDTH {
+            let biggest = children
+                .iter()
+                .enumerate()
+                .filter(|(_, &child)| binary[child].count == 0)
+                .max_by(|(_, &a), (_, &b)| {
+                    surface_area(&binary[a]).total_cmp(&surface_area(&binary[b]))
+                });
+            match biggest {
+                Some((i, &child)) => {
+                    children.splice(i..=i, [child + 1, binary[child].offset as usize]);
+                }
+                None => break,
+            }
+        }
+
+        let wide_index = nodes.len();
+        nodes.push(WideNode::empty());
+        let mut node = WideNode::empty();
+        for (slot, &child) in children.iter().enumerate() {
+            let binary_node = &binary[child];
+            for a in 0..=2 {
+                node.minimum[a][slot] = binary_node.minimum[a];
+                node.maximum[a][slot] = binary_node.maximum[a];
+            }
+            if binary_node.count > 0 {
+                node.offsets[slot] = binary_node.offset;
+                node.counts[slot] = binary_node.count;
+            } else {
+                node.offsets[slot] = WideBVH::collapse(binary, child, nodes) as u32;
+            }
+        }
+        nodes[wide_index] = node;
+        wide_index
+    }
+}
+
+impl Object for WideBVH {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let wide_ray = WideRay::new(ray);
+        let wide_t_min = t_min as f32;
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+        let mut visited = 0;
+
+        // Each entry is a node or leaf's offset and count, and where the ray enters its box.
+        // Zeroing the whole stack for every ray would cost more than the traversal saves.
+        let mut stack = [MaybeUninit::<(u32, u16, f32)>::uninit(); STACK_SIZE];
+        stack[0] = MaybeUninit::new((0, 0, wide_t_min));
+        let mut stack_len = 1;
+        while stack_len > 0 {
+            stack_len -= 1;
+            // Everything below `stack_len` has been written
+            let (offset, count, t_enter) = unsafe { stack[stack_len].assume_init() };
+            if t_enter as f64 > closest_so_far {
+                // Something nearer turned up since this was pushed
+                continue;
+            }
+            if count > 0 {
+                let start = offset as usize;
+                for object in &self.objects[start..start + count as usize] {
+                    if object.hit(ray, t_min, closest_so_far, rec, rng) {
+                        hit_anything = true;
+                        closest_so_far = rec.t;
+                    }
+                }
+                continue;
+            }
+
+            visited += 1;
+            let node = &self.nodes[offset as usize];
+            let t_enters = node.intersect(&wide_ray, wide_t_min, round_up(closest_so_far));
+            // Push the children that were hit furthest first, so the nearest is visited next
+            let mut hits = [(0.0, 0); WIDTH];
+            let mut hit_count = 0;
+            for (slot, &t_enter) in t_enters.iter().enumerate() {
+                if t_enter == f32::INFINITY {
+                    continue;
+                }
+                let mut i = hit_count;
+                while i > 0 && hits[i - 1].0 < t_enter {
+                    hits[i] = hits[i - 1];
+                    i -= 1;
+                }
+                hits[i] = (t_enter, slot);
+                hit_count += 1;
+            }
+            for &(t_enter, slot) in &hits[..hit_count] {
+                stack[stack_len] =
+                    MaybeUninit::new((node.offsets[slot], node.counts[slot], t_enter));
+                stack_len += 1;
+            }
+        }
+
+        add_nodes_visited(visited);
+        hit_anything
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        self.bounding_box
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::object::hit_t;
+    use crate::object::{MeshData, ObjectList, Sphere, Triangle};
+    use crate::vec3::Vec3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_matches_object_list() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let mut objects: Vec<Arc<dyn Object + Send + Sync>> = vec![Arc::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            material.clone(),
+        ))];
+        for _ in 0..300 {
+            let center = Vec3::random(&mut rng, -10.0, 10.0);
+            let radius = rng.gen_range(0.05..1.0);
+            objects.push(Arc::new(Sphere::new(center, radius, material.clone())));
+        }
+        let tree = WideBVH::new(&objects, 0.0, 0.0);
+        let list = ObjectList::new(objects);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = Ray::new(
+                Vec3::random(&mut rng, -15.0, 15.0),
+                Vec3::random_unit_vector(&mut rng),
+                0.0,
+            );
+            let expected = hit_t(&list, &ray, &mut rng);
+            assert_eq!(hit_t(&tree, &ray, &mut rng), expected);
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 500);
+
+        // Rays along the axes divide by zero in the other two
+        let ray = Ray::new(Vec3::new(0.0, 20.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert_eq!(hit_t(&tree, &ray, &mut rng), hit_t(&list, &ray, &mut rng));
+    }
+
+    #[test]
+    fn test_scalar_matches_sse() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let mut node = WideNode::empty();
+            // Leave the last slot empty
+            for slot in 0..WIDTH - 1 {
+                let corner = Vec3::random(&mut rng, -5.0, 5.0);
+                let size = Vec3::random(&mut rng, 0.0, 2.0);
+                for a in 0..=2 {
+                    node.minimum[a][slot] = corner[a] as f32;
+                    node.maximum[a][slot] = (corner[a] + size[a]) as f32;
+                }
+            }
+            // Often with a zero component, for infinite reciprocals
+            let direction = Vec3::random_unit_vector(&mut rng);
+            let direction = match rng.gen_range(0..4) {
+                0 => Vec3::new(0.0, direction.y, direction.z),
+                1 => Vec3::new(direction.x, 0.0, direction.z),
+                2 => Vec3::new(direction.x, direction.y, 0.0),
+                _ => direction,
+            };
+            let ray = WideRay::new(&Ray::new(
+                Vec3::random(&mut rng, -10.0, 10.0),
+                direction,
+                0.0,
+            ));
+            let expected = node.intersect_scalar(&ray, 0.001, f32::INFINITY);
+            assert_eq!(node.intersect(&ray, 0.001, f32::INFINITY), expected);
+            assert_eq!(expected[WIDTH - 1], f32::INFINITY);
+        }
+    }
+
+    #[test]
+    fn test_packed_triangles() {
+        // Small triangles scattered through a box
+        let mut rng = StdRng::seed_from_u64(1);
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let objects: Vec<Arc<dyn Object + Send + Sync>> = (0..500)
+            .map(|_| -> Arc<dyn Object + Send + Sync> {
+                let center = Vec3::random(&mut rng, -10.0, 10.0);
+                let mesh = Arc::new(MeshData {
+                    positions: (0..3)
+                        .map(|_| center + Vec3::random(&mut rng, -3.0, 3.0))
+                        .collect(),
+                    normals: vec![],
+                    uvs: vec![],
+                });
+                Arc::new(Triangle::new(mesh, [0, 1, 2], material.clone()))
+            })
+            .collect();
+        let tree = WideBVH::new(&objects, 0.0, 0.0);
+        assert!(tree.objects.len() < objects.len());
+        let list = ObjectList::new(objects);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = Ray::new(
+                Vec3::random(&mut rng, -15.0, 15.0),
+                Vec3::random_unit_vector(&mut rng),
+                0.0,
+            );
+            let expected = hit_t(&list, &ray, &mut rng);
+            assert_eq!(hit_t(&tree, &ray, &mut rng), expected);
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 200);
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(std::mem::size_of::<WideNode>(), 128);
+        assert_eq!(std::mem::align_of::<WideNode>(), 64);
+
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let sphere = |x: f64| -> Arc<dyn Object + Send + Sync> {
+            Arc::new(Sphere::new(Vec3::new(x, 0.0, 0.0), 1.0, material.clone()))
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let ray = Ray::new(Vec3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+
+        // A single leaf still gets a node of its own
+        let tree = WideBVH::new(&[sphere(0.0)], 0.0, 0.0);
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(hit_t(&tree, &ray, &mut rng), Some(9.0));
+
+        // Two levels of a binary tree go into one node
+        let tree = WideBVH::new(
+            &[sphere(0.0), sphere(100.0), sphere(200.0), sphere(300.0)],
+            0.0,
+            0.0,
+        );
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(hit_t(&tree, &ray, &mut rng), Some(9.0));
+
+        let empty = WideBVH::new(&[], 0.0, 0.0);
+        assert!(empty.bounding_box(0.0, 0.0).is_none());
+        assert_eq!(hit_t(&empty, &ray, &mut rng), None);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::aabb::AABB;
+use crate::material::Material;
+use crate::object::{HitRecord, Object};
+use crate::ray::Ray;
+use crate::scene::{hash, real, string, SceneWriter};
+use crate::vec3::Vec3;
+use rand::{Rng, RngCore};
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// A volume of smoke or mist filling a boundary object, which has to be convex: a ray is
+/// assumed to be inside between the first two times it crosses the boundary.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Object + Send + Sync>,
+    density: f64,
+    /// How the medium scatters light, normally `Isotropic`
+    phase_function: Arc<dyn Material + Send + Sync>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Object + Send + Sync>,
+        density: f64,
+        phase_function: Arc<dyn Material + Send + Sync>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+
+/// Samples how far a ray travels through a medium of `density` before it's scattered.
+pub fn free_flight_distance(density: f64, rng: &mut dyn RngCore) -> f64 {
+    // 1 - x is in (0, 1], so the log is finite
+    -(1.0 - rng.gen::<f64>()).ln() / density
+}
+
+impl Object for ConstantMedium {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        // Find where the ray's line enters and leaves the boundary, even if it starts inside
+        let mut entry = HitRecord::new(self.phase_function.clone());
+        if !self
+            .boundary
+            .hit(ray, f64::NEG_INFINITY, f64::INFINITY, &mut entry, rng)
+        {
+            return false;
+        }
+        let entered = entry.t;
+        if !self
+            .boundary
+            .hit(ray, entered + 0.0001, f64::INFINITY, &mut entry, rng)
+        {
+            return false;
+        }
+        let (entered, left) = (entered.max(t_min), entry.t.min(t_max));
+        if entered >= left {
+            return false;
+        }
+
+        let ray_length = ray.direction.len();
+        let distance_inside = (left - entered) * ray_length;
+        let hit_distance = free_flight_distance(self.density, rng);
+        if hit_distance > distance_inside {
+            return false;
+        }
+
+        rec.t = entered + hit_distance / ray_length;
+        rec.p = ray.at(rec.t);
+        // Neither matters to an isotropic phase function
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.u = 0.0;
+        rec.v = 0.0;
+        rec.material = self.phase_function.clone();
+
+        true
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.boundary.bounding_box(time0, time1)
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("constant_medium")),
+            ("density", real(self.density)),
+            ("material", writer.material(&self.phase_function)?),
+            ("boundary", self.boundary.to_yaml(writer)?),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Isotropic;
+    use crate::object::Sphere;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_transmittance() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let material = Arc::new(Isotropic::new(Color::new(1.0, 1.0, 1.0)));
+        let boundary = Arc::new(Sphere::new(Vec3::origin(), 1.0, material.clone()));
+        let medium = ConstantMedium::new(boundary, 0.5, material.clone());
+        let mut rec = HitRecord::new(material);
+
+        // Straight through the middle there's 2 units of medium, which lets e^-1 of the rays
+        // through. Starting inside, only half of it is in the way.
+        let transmitted = |origin: Vec3, rng: &mut StdRng, rec: &mut HitRecord| {
+            let ray = Ray::new(origin, Vec3::new(0.0, 0.0, -2.0), 0.0);
+            let samples = 20000;
+            let misses = (0..samples)
+                .filter(|_| !medium.hit(&ray, 0.001, f64::INFINITY, rec, rng))
+                .count();
+            misses as f64 / samples as f64
+        };
+        let through = transmitted(Vec3::new(0.0, 0.0, 5.0), &mut rng, &mut rec);
+        assert!((through - (-1.0f64).exp()).abs() < 0.01, "{}", through);
+        let from_middle = transmitted(Vec3::origin(), &mut rng, &mut rec);
+        assert!(
+            (from_middle - (-0.5f64).exp()).abs() < 0.01,
+            "{}",
+            from_middle
+        );
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        while !medium.hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng) {}
+        assert!((4.0..=6.0).contains(&rec.t));
+        assert!(!medium.hit(&ray, 0.001, 3.5, &mut rec, &mut rng));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::color::Color;
+use crate::material::{Isotropic, Material};
+use crate::object::constant_medium::constant_medium::free_flight_distance;
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::scene::{color, hash, real};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// The distance from the origin that fog reaches unless a scene says otherwise.
+pub const DEFAULT_FOG_EXTENT: f64 = 100.0;
+
+/// Haze filling the whole scene out to `extent` from the origin. Surfaces and the background
+/// beyond it are seen through the same depth of fog, so distant ground fades into the sky.
+pub struct Fog {
+    density: f64,
+    color: Color,
+    extent: f64,
+    phase_function: Arc<dyn Material + Send + Sync>,
+}
+
+impl Fog {
+    pub fn new(density: f64, color: Color, extent: f64) -> Fog {
+        Fog {
+            density,
+            color,
+            extent,
+            phase_function: Arc::new(Isotropic::new(color)),
+        }
+    }
+
+    /// Scatters a ray somewhere between `t_min` and `t_max`, where it reaches a surface or
+    /// heads off to the background, if it passes through enough fog on the way, updating
+    /// `rec` to match.
+    pub fn scatter(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        // Clip the ray to the ball the fog fills
+        let a = ray.direction.len_squared();
+        let half_b = Vec3::dot(&ray.origin, &ray.direction);
+        let c = ray.origin.len_squared() - self.extent * self.extent;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant <= 0.0 {
+            return false;
+        }
+        let root = discriminant.sqrt();
+        let t_enter = ((-half_b - root) / a).max(t_min);
+        let t_exit = ((-half_b + root) / a).min(t_max);
+
+        let t = t_enter + free_flight_distance(self.density, rng) / a.sqrt();
+        if t >= t_exit {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.u = 0.0;
+        rec.v = 0.0;
+        rec.material = self.phase_function.clone();
+
+        true
+    }
+
+    pub fn to_yaml(&self) -> Yaml {
+        hash(vec![
+            ("density", real(self.density)),
+            ("color", color(&self.color)),
+            ("extent", real(self.extent)),
+        ])
+    }
+}
@@ -0,0 +1,4 @@
+mod constant_medium;
+pub use constant_medium::{free_flight_distance, ConstantMedium};
+mod fog;
+pub use fog::{Fog, DEFAULT_FOG_EXTENT};
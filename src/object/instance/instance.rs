@@ -0,0 +1,214 @@
+use crate::aabb::AABB;
+use crate::matrix::Matrix;
+use crate::object::{HitRecord, Object};
+use crate::ray::Ray;
+use crate::scene::{hash, real, string, SceneWriter};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// Places an object in the scene through an affine transform, so the same geometry can be
+/// shared by any number of instances.
+pub struct Instance {
+    object: Arc<dyn Object + Send + Sync>,
+    transform: Matrix,
+    inverse: Matrix,
+    /// Carries normals out of object space
+    normal_matrix: Matrix,
+}
+
+impl Instance {
+    /// Panics if `transform` can't be inverted.
+    pub fn new(object: Arc<dyn Object + Send + Sync>, transform: Matrix) -> Instance {
+        let inverse = transform
+            .inverse()
+            .expect("an instance's transform must be invertible");
+        Instance {
+            object,
+            transform,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        }
+    }
+}
+
+impl Object for Instance {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        // The direction isn't normalized, so distances along the ray are the same in both
+        // spaces.
+        let object_ray = Ray::new(
+            self.inverse.transform_point(&ray.origin),
+            self.inverse.transform_vector(&ray.direction),
+            ray.time,
+        );
+        if !self.object.hit(&object_ray, t_min, t_max, rec, rng) {
+            return false;
+        }
+
+        rec.p = self.transform.transform_point(&rec.p);
+        let outward_normal = if rec.front_face {
+            rec.normal
+        } else {
+            -rec.normal
+        };
+        let outward_normal =
+            Vec3::unit_vector(&self.normal_matrix.transform_vector(&outward_normal));
+        rec.set_face_normal(ray, outward_normal);
+
+        true
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        let bounds = self.object.bounding_box(time0, time1)?;
+        let corner = |idx: usize| {
+            let pick = |bit: usize, min: f64, max: f64| if idx & bit == 0 { min } else { max };
+            self.transform.transform_point(&Vec3::new(
+                pick(1, bounds.minimum.x, bounds.maximum.x),
+                pick(2, bounds.minimum.y, bounds.maximum.y),
+                pick(4, bounds.minimum.z, bounds.maximum.z),
+            ))
+        };
+        let first = corner(0);
+        Some(
+            (1..8)
+                .map(corner)
+                .fold(AABB::new(first, first), |bounds, p| {
+                    AABB::bounding_box(&bounds, &AABB::new(p, p))
+                }),
+        )
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    fn sample_direction(&self, origin: &Vec3, rng: &mut dyn RngCore) -> Option<Vec3> {
+        let direction = self
+            .object
+            .sample_direction(&self.inverse.transform_point(origin), rng)?;
+        Some(self.transform.transform_vector(&direction))
+    }
+
+    fn pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        // The transform bends directions as well as carrying them over, packing more of them
+        // into some parts of the sphere than others: the density changes by |det A| / |Aw|^3
+        // for a unit direction w taken into object space by A.
+        let object_direction = self.inverse.transform_vector(&Vec3::unit_vector(direction));
+        let stretch = object_direction.len();
+        let object_pdf = self.object.pdf(
+            &self.inverse.transform_point(origin),
+            &(object_direction / stretch),
+        );
+        object_pdf * self.inverse.determinant().abs() / (stretch * stretch * stretch)
+    }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Object + Send + Sync>>) {
+        // Lights inside are placed through this transform too
+        let mut inside = vec![];
+        self.object.collect_lights(&mut inside);
+        lights.extend(
+            inside
+                .into_iter()
+                .map(|light| -> Arc<dyn Object + Send + Sync> {
+                    Arc::new(Instance::new(light, self.transform))
+                }),
+        );
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        let rows = self
+            .transform
+            .rows
+            .iter()
+            .map(|row| Yaml::Array(row.iter().map(|value| real(*value)).collect()))
+            .collect();
+        Ok(hash(vec![
+            ("type", string("instance")),
+            ("transform", Yaml::Array(rows)),
+            ("object", writer.model(&self.object)?),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::object::Sphere;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_instance() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let sphere = Arc::new(Sphere::new(Vec3::origin(), 1.0, material.clone()));
+        // Stretched into an ellipsoid 4 wide, then stood up and moved back
+        let instance = Instance::new(
+            sphere,
+            Matrix::translation(&Vec3::new(0.0, 0.0, -10.0))
+                * Matrix::rotation_z(90.0)
+                * Matrix::scaling(&Vec3::new(2.0, 1.0, 1.0)),
+        );
+
+        let mut rec = HitRecord::new(material);
+        let down = Ray::new(Vec3::new(0.0, 5.0, -10.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(instance.hit(&down, 0.001, f64::INFINITY, &mut rec, &mut rng));
+        assert!((rec.t - 3.0).abs() < 1e-9);
+        assert!((rec.p - Vec3::new(0.0, 2.0, -10.0)).len() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-9);
+        assert!(rec.front_face);
+
+        // Halfway up, the stretched sphere's normal is much flatter than a round one's would be
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(instance.hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng));
+        let expected = Vec3::unit_vector(&Vec3::new(0.0, 0.25, 0.75f64.sqrt()));
+        assert!((rec.normal - expected).len() < 1e-9);
+
+        let bounds = instance.bounding_box(0.0, 0.0).unwrap();
+        assert!((bounds.minimum - Vec3::new(-1.0, -2.0, -11.0)).len() < 1e-9);
+        assert!((bounds.maximum - Vec3::new(1.0, 2.0, -9.0)).len() < 1e-9);
+    }
+
+    #[test]
+    fn test_instanced_light() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let light = Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)));
+        let sphere = Arc::new(Sphere::new(Vec3::origin(), 1.0, light.clone()));
+        let instance = Instance::new(
+            sphere,
+            Matrix::translation(&Vec3::new(0.0, 0.0, -4.0))
+                * Matrix::rotation_y(30.0)
+                * Matrix::scaling(&Vec3::new(2.0, 1.0, 0.5)),
+        );
+        assert!(instance.is_light());
+
+        // Directions picked point at the stretched sphere
+        let origin = Vec3::origin();
+        let mut rec = HitRecord::new(light);
+        for _ in 0..100 {
+            let direction = instance.sample_direction(&origin, &mut rng).unwrap();
+            let ray = Ray::new(origin, direction, 0.0);
+            assert!(instance.hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng));
+            assert!(instance.pdf(&origin, &direction) > 0.0);
+        }
+
+        // And the density still adds up to 1 over every direction
+        let samples = 200000;
+        let total: f64 = (0..samples)
+            .map(|_| instance.pdf(&origin, &Vec3::random_unit_vector(&mut rng)))
+            .sum();
+        let integral = total * 4.0 * std::f64::consts::PI / samples as f64;
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+}
@@ -0,0 +1,2 @@
+mod instance;
+pub use instance::Instance;
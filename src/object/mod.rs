@@ -1,8 +1,28 @@
+mod box_shape;
+pub use box_shape::BoxShape;
 mod bvh;
-pub use bvh::BVHNode;
+pub use bvh::{build_time, nodes_visited, BVH};
+mod constant_medium;
+pub use constant_medium::{ConstantMedium, Fog, DEFAULT_FOG_EXTENT};
+mod instance;
+pub use instance::Instance;
+mod moving_sphere;
+pub use moving_sphere::MovingSphere;
 mod object;
-pub use object::{HitRecord, Object};
+#[cfg(test)]
+pub use object::hit_t;
+pub use object::{find_lights, HitRecord, Object};
 mod object_list;
 pub use object_list::ObjectList;
+mod quad;
+pub use quad::Quad;
 mod sphere;
-pub use sphere::Sphere;
+pub use sphere::{sphere_uv, Sphere};
+mod triangle;
+pub use triangle::Triangle;
+#[cfg(feature = "simd")]
+pub use triangle::Triangle4;
+mod triangle_mesh;
+pub use triangle_mesh::{MeshData, TriangleMesh};
+mod voxel_volume;
+pub use voxel_volume::{VoxelGrid, VoxelVolume};
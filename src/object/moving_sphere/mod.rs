@@ -0,0 +1,2 @@
+mod moving_sphere;
+pub use moving_sphere::MovingSphere;
@@ -0,0 +1,156 @@
+use crate::aabb::AABB;
+use crate::material::Material;
+use crate::object::{sphere_uv, HitRecord, Object};
+use crate::ray::Ray;
+use crate::scene::{hash, real, string, vec3, SceneWriter};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// A sphere that moves in a straight line from `center0` at `time0` to `center1` at `time1`,
+/// resting at either end outside of that interval.
+pub struct MovingSphere {
+    center0: Vec3,
+    center1: Vec3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    pub material: Arc<dyn Material + Send + Sync>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Vec3,
+        center1: Vec3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Vec3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        let t = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + t * &(self.center1 - self.center0)
+    }
+}
+
+impl Object for MovingSphere {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        let center = self.center(ray.time);
+        let oc = ray.origin - center;
+        let a = ray.direction.len_squared();
+        let half_b = Vec3::dot(&oc, &ray.direction);
+        let c = oc.len_squared() - self.radius * self.radius;
+
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return false;
+        };
+        let sqrtd = discriminant.sqrt();
+
+        // Find the nearest root that lies in the acceptable range.
+        let mut root = (-half_b - sqrtd) / a;
+        if root < t_min || t_max < root {
+            root = (-half_b + sqrtd) / a;
+            if root < t_min || t_max < root {
+                return false;
+            }
+        }
+
+        rec.t = root;
+        rec.p = ray.at(rec.t);
+        let outward_normal = (rec.p - center) / self.radius;
+        rec.set_face_normal(ray, outward_normal);
+        let (u, v) = sphere_uv(&outward_normal);
+        rec.u = u;
+        rec.v = v;
+        rec.material = self.material.clone();
+
+        true
+    }
+
+    /// Covers the whole of the motion, whatever the interval asked for, so the box stays valid
+    /// for any ray time.
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        let radius = Vec3::new(self.radius, self.radius, self.radius);
+        let box0 = AABB::new(self.center0 - radius, self.center0 + radius);
+        let box1 = AABB::new(self.center1 - radius, self.center1 + radius);
+        Some(AABB::bounding_box(&box0, &box1))
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("moving_sphere")),
+            ("center0", vec3(&self.center0)),
+            ("center1", vec3(&self.center1)),
+            ("time0", real(self.time0)),
+            ("time1", real(self.time1)),
+            ("radius", real(self.radius)),
+            ("material", writer.material(&self.material)?),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_motion() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let sphere = MovingSphere::new(
+            Vec3::new(0.0, 0.0, -2.0),
+            Vec3::new(4.0, 0.0, -2.0),
+            0.0,
+            1.0,
+            0.5,
+            material.clone(),
+        );
+        assert_eq!(sphere.center(0.25), Vec3::new(1.0, 0.0, -2.0));
+        assert_eq!(sphere.center(2.0), Vec3::new(4.0, 0.0, -2.0));
+
+        let ray =
+            |x: f64, time: f64| Ray::new(Vec3::new(x, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), time);
+        let mut rec = HitRecord::new(material);
+        assert!(!sphere.hit(&ray(4.0, 0.0), 0.001, f64::INFINITY, &mut rec, &mut rng));
+        assert!(sphere.hit(&ray(4.0, 1.0), 0.001, f64::INFINITY, &mut rec, &mut rng));
+        assert!((rec.t - 1.5).abs() < 1e-9);
+
+        // The box has to cover both ends of the motion and everything in between
+        let bounds = sphere.bounding_box(0.0, 0.0).unwrap();
+        for x in [-0.25, 2.0, 4.25] {
+            assert!(bounds
+                .intersect(&ray(x, 0.0), 0.001, f64::INFINITY)
+                .is_some());
+        }
+        assert!(bounds
+            .intersect(&ray(4.75, 0.0), 0.001, f64::INFINITY)
+            .is_none());
+    }
+}
@@ -1,18 +1,38 @@
 use crate::aabb::AABB;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::scene::{unsupported, SceneWriter};
 use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
 use std::sync::Arc;
+use yaml_rust::Yaml;
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub p: Vec3,
     pub normal: Vec3,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
     pub material: Arc<dyn Material>,
 }
 
 impl HitRecord {
+    /// A record to be filled in by `Object::hit`, with `material` until something is hit.
+    pub fn new(material: Arc<dyn Material>) -> HitRecord {
+        HitRecord {
+            p: Vec3::origin(),
+            normal: Vec3::origin(),
+            t: 0.0,
+            u: 0.0,
+            v: 0.0,
+            front_face: false,
+            material,
+        }
+    }
+
     pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: Vec3) {
         self.front_face = Vec3::dot(&ray.direction, &outward_normal) < 0.0;
         self.normal = if self.front_face {
@@ -24,6 +44,65 @@ impl HitRecord {
 }
 
 pub trait Object {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool;
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB>;
+    /// Whether the object gives off light and can be aimed at with `sample_direction`.
+    fn is_light(&self) -> bool {
+        false
+    }
+    /// Picks a direction from `origin` towards a random point on the object, for objects that
+    /// can be sampled.
+    fn sample_direction(&self, _origin: &Vec3, _rng: &mut dyn RngCore) -> Option<Vec3> {
+        None
+    }
+    /// The density, per unit solid angle, with which `sample_direction` picks `direction`.
+    fn pdf(&self, _origin: &Vec3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+    /// The corners of objects that are a single triangle, so that several can be tested at
+    /// once.
+    #[cfg(feature = "simd")]
+    fn triangle(&self) -> Option<[Vec3; 3]> {
+        None
+    }
+    /// Adds the lights held inside this object to `lights`, for objects made of others.
+    fn collect_lights(&self, _lights: &mut Vec<Arc<dyn Object + Send + Sync>>) {}
+    fn to_yaml(&self, _writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Err(unsupported("object"))
+    }
+}
+
+/// Adds the objects that give off light to `lights`, looking inside the ones that hold others.
+pub fn find_lights(
+    objects: &[Arc<dyn Object + Send + Sync>],
+    lights: &mut Vec<Arc<dyn Object + Send + Sync>>,
+) {
+    for object in objects {
+        if object.is_light() {
+            lights.push(object.clone());
+        } else {
+            object.collect_lights(lights);
+        }
+    }
+}
+
+/// How far along `ray` `object` is first hit, if it is, for comparing objects in tests.
+#[cfg(test)]
+pub fn hit_t(object: &dyn Object, ray: &Ray, rng: &mut dyn RngCore) -> Option<f64> {
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    let mut rec = HitRecord::new(Arc::new(Lambertian::new(Color::default())));
+    if object.hit(ray, 0.001, f64::INFINITY, &mut rec, rng) {
+        Some(rec.t)
+    } else {
+        None
+    }
 }
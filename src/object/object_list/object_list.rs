@@ -1,8 +1,12 @@
 use crate::aabb::AABB;
-use crate::object::{HitRecord, Object};
+use crate::object::{find_lights, HitRecord, Object};
 use crate::ray::Ray;
+use crate::scene::{hash, string, SceneWriter};
 use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
 use std::sync::Arc;
+use yaml_rust::Yaml;
 
 #[derive(Clone)]
 pub struct ObjectList {
@@ -16,18 +20,25 @@ impl ObjectList {
 }
 
 impl Object for ObjectList {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
 
         for obj in &self.objects {
-            if obj.hit(ray, t_min, closest_so_far, rec) {
+            if obj.hit(ray, t_min, closest_so_far, rec, rng) {
                 hit_anything = true;
                 closest_so_far = rec.t;
             }
         }
 
-        return hit_anything;
+        hit_anything
     }
 
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
@@ -54,4 +65,15 @@ impl Object for ObjectList {
 
         Some(output_box)
     }
+
+    fn collect_lights(&self, lights: &mut Vec<Arc<dyn Object + Send + Sync>>) {
+        find_lights(&self.objects, lights);
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("list")),
+            ("objects", writer.objects(&self.objects)?),
+        ]))
+    }
 }
@@ -0,0 +1,2 @@
+mod quad;
+pub use quad::Quad;
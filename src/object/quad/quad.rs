@@ -0,0 +1,176 @@
+use crate::aabb::AABB;
+use crate::material::Material;
+use crate::object::{HitRecord, Object};
+use crate::ray::Ray;
+use crate::scene::{hash, string, vec3, SceneWriter};
+use crate::vec3::Vec3;
+use rand::{Rng, RngCore};
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// A parallelogram with one corner at `corner` and its two edges from there along `u` and `v`.
+/// It faces along `u × v`, and its texture coordinates run from 0 to 1 along each edge.
+pub struct Quad {
+    corner: Vec3,
+    u: Vec3,
+    v: Vec3,
+    pub material: Arc<dyn Material + Send + Sync>,
+    normal: Vec3,
+    /// The plane's distance from the origin along `normal`
+    d: f64,
+    /// `u × v` scaled so that dotting it with a point on the plane's cross product with an edge
+    /// gives that point's coordinate along the other edge.
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3, material: Arc<dyn Material + Send + Sync>) -> Quad {
+        let n = Vec3::cross(&u, &v);
+        let normal = Vec3::unit_vector(&n);
+        Quad {
+            corner,
+            u,
+            v,
+            material,
+            normal,
+            d: Vec3::dot(&normal, &corner),
+            w: n / Vec3::dot(&n, &n),
+            area: n.len(),
+        }
+    }
+
+    /// Finds where the ray crosses the quad, if it does between `t_min` and `t_max`, returning
+    /// the distance along the ray and the coordinates along each edge.
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+        let denom = Vec3::dot(&self.normal, &ray.direction);
+        if denom.abs() < 1e-12 {
+            // The ray is parallel to the plane
+            return None;
+        }
+
+        let t = (self.d - Vec3::dot(&self.normal, &ray.origin)) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let offset = ray.at(t) - self.corner;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&offset, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &offset));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some((t, alpha, beta))
+    }
+}
+
+impl Object for Quad {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        let (t, alpha, beta) = match self.intersect(ray, t_min, t_max) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        rec.set_face_normal(ray, self.normal);
+        rec.u = alpha;
+        rec.v = beta;
+        rec.material = self.material.clone();
+
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        let corners = [
+            self.corner + self.u,
+            self.corner + self.v,
+            self.corner + self.u + self.v,
+        ];
+        let bounds = corners
+            .iter()
+            .fold(AABB::new(self.corner, self.corner), |bounds, p| {
+                AABB::bounding_box(&bounds, &AABB::new(*p, *p))
+            });
+        Some(bounds.padded(1e-4))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_direction(&self, origin: &Vec3, rng: &mut dyn RngCore) -> Option<Vec3> {
+        let p = self.corner + rng.gen::<f64>() * &self.u + rng.gen::<f64>() * &self.v;
+        Some(p - *origin)
+    }
+
+    fn pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        match self.intersect(&ray, 0.001, f64::INFINITY) {
+            // Points are picked evenly over the area, which looks smaller the further away and
+            // more side on it is.
+            Some((t, _, _)) => {
+                let distance_squared = t * t * direction.len_squared();
+                let cosine = Vec3::dot(&self.normal, direction).abs() / direction.len();
+                distance_squared / (cosine * self.area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("quad")),
+            ("corner", vec3(&self.corner)),
+            ("u", vec3(&self.u)),
+            ("v", vec3(&self.v)),
+            ("material", writer.material(&self.material)?),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_hit() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let material = Arc::new(Lambertian::new(Color::default()));
+        let quad = Quad::new(
+            Vec3::new(-1.0, -1.0, -2.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 4.0, 0.0),
+            material.clone(),
+        );
+        let mut rec = HitRecord::new(material);
+        let ray = |x: f64, y: f64| Ray::new(Vec3::new(x, y, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+
+        assert!(quad.hit(&ray(0.5, 2.0), 0.001, f64::INFINITY, &mut rec, &mut rng));
+        assert_eq!(rec.t, 2.0);
+        assert_eq!((rec.u, rec.v), (0.75, 0.75));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+        assert!(!quad.hit(&ray(1.5, 0.0), 0.001, f64::INFINITY, &mut rec, &mut rng));
+        assert!(!quad.hit(&ray(0.0, -1.5), 0.001, f64::INFINITY, &mut rec, &mut rng));
+        assert!(!quad.hit(&ray(0.0, 0.0), 0.001, 1.0, &mut rec, &mut rng));
+
+        // Flat as it is, its box still has to be hit
+        let bounds = quad.bounding_box(0.0, 0.0).unwrap();
+        assert!(bounds
+            .intersect(&ray(0.0, 0.0), 0.001, f64::INFINITY)
+            .is_some());
+    }
+}
@@ -1,2 +1,2 @@
 mod sphere;
-pub use sphere::Sphere;
+pub use sphere::{sphere_uv, Sphere};
@@ -2,8 +2,13 @@ use crate::aabb::AABB;
 use crate::material::Material;
 use crate::object::{HitRecord, Object};
 use crate::ray::Ray;
+use crate::scene::{hash, real, string, vec3, SceneWriter};
 use crate::vec3::Vec3;
+use rand::{Rng, RngCore};
+use std::f64::consts::PI;
+use std::io::Result as IoResult;
 use std::sync::Arc;
+use yaml_rust::Yaml;
 
 pub struct Sphere {
     center: Vec3,
@@ -19,10 +24,32 @@ impl Sphere {
             material,
         }
     }
+
+    /// 1 - cos θ, for the angle θ between the center and the edge of the sphere as seen from
+    /// `distance_squared` away, written to keep its precision for small, distant spheres.
+    fn cone_size(&self, distance_squared: f64) -> f64 {
+        let sin_squared = self.radius * self.radius / distance_squared;
+        sin_squared / (1.0 + (1.0 - sin_squared).sqrt())
+    }
+}
+
+/// Finds the texture coordinates of a point on the unit sphere: `u` goes once around the y axis
+/// starting from -x, and `v` goes from the bottom to the top.
+pub fn sphere_uv(p: &Vec3) -> (f64, f64) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
 }
 
 impl Object for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
         let oc = ray.origin - self.center;
         let a = ray.direction.len_squared();
         let half_b = Vec3::dot(&oc, &ray.direction);
@@ -47,6 +74,9 @@ impl Object for Sphere {
         rec.p = ray.at(rec.t);
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(ray, outward_normal);
+        let (u, v) = sphere_uv(&outward_normal);
+        rec.u = u;
+        rec.v = v;
         rec.material = self.material.clone();
 
         true
@@ -58,4 +88,73 @@ impl Object for Sphere {
             self.center + Vec3::new(self.radius, self.radius, self.radius),
         ))
     }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_direction(&self, origin: &Vec3, rng: &mut dyn RngCore) -> Option<Vec3> {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.len_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Some(Vec3::random_unit_vector(rng));
+        }
+
+        // Pick evenly within the cone of directions the sphere covers
+        let cos_theta = 1.0 - rng.gen::<f64>() * self.cone_size(distance_squared);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let w = Vec3::unit_vector(&to_center);
+        let axis = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::unit_vector(&Vec3::cross(&w, &axis));
+        let u = Vec3::cross(&w, &v);
+        Some((phi.cos() * sin_theta) * &u + (phi.sin() * sin_theta) * &v + cos_theta * &w)
+    }
+
+    fn pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.len_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 1.0 / (4.0 * PI);
+        }
+
+        let cone_size = self.cone_size(distance_squared);
+        let cosine = Vec3::dot(direction, &to_center) / (direction.len() * distance_squared.sqrt());
+        if 1.0 - cosine > cone_size {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * cone_size)
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("sphere")),
+            ("center", vec3(&self.center)),
+            ("radius", real(self.radius)),
+            ("material", writer.material(&self.material)?),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sphere_uv() {
+        let uv = |x: f64, y: f64, z: f64| {
+            let (u, v) = sphere_uv(&Vec3::new(x, y, z));
+            ((u * 1e9).round() / 1e9, (v * 1e9).round() / 1e9)
+        };
+        assert_eq!(uv(-1.0, 0.0, 0.0), (0.0, 0.5));
+        assert_eq!(uv(0.0, 0.0, 1.0), (0.25, 0.5));
+        assert_eq!(uv(1.0, 0.0, 0.0), (0.5, 0.5));
+        assert_eq!(uv(0.0, 0.0, -1.0), (0.75, 0.5));
+        assert_eq!(uv(0.0, 1.0, 0.0).1, 1.0);
+        assert_eq!(uv(0.0, -1.0, 0.0).1, 0.0);
+    }
 }
@@ -0,0 +1,6 @@
+mod triangle;
+pub use triangle::Triangle;
+#[cfg(feature = "simd")]
+mod triangle4;
+#[cfg(feature = "simd")]
+pub use triangle4::Triangle4;
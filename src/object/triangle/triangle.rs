@@ -0,0 +1,242 @@
+use crate::aabb::AABB;
+use crate::material::Material;
+use crate::object::{HitRecord, MeshData, Object};
+use crate::ray::Ray;
+use crate::scene::{hash, string, uv, vec3, SceneWriter};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+pub struct Triangle {
+    mesh: Arc<MeshData>,
+    indices: [usize; 3],
+    pub material: Arc<dyn Material + Send + Sync>,
+}
+
+impl Triangle {
+    /// A triangle made from three of the vertices of a shared mesh.
+    pub fn new(
+        mesh: Arc<MeshData>,
+        indices: [usize; 3],
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Triangle {
+        Triangle {
+            mesh,
+            indices,
+            material,
+        }
+    }
+
+    fn positions(&self) -> [Vec3; 3] {
+        [
+            self.mesh.positions[self.indices[0]],
+            self.mesh.positions[self.indices[1]],
+            self.mesh.positions[self.indices[2]],
+        ]
+    }
+}
+
+impl Object for Triangle {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        // Möller–Trumbore: solve for the distance along the ray and the barycentric
+        // coordinates of the hit in one go.
+        let [p0, p1, p2] = self.positions();
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+
+        let h = Vec3::cross(&ray.direction, &edge2);
+        let det = Vec3::dot(&edge1, &h);
+        if det.abs() < 1e-12 {
+            // The ray is parallel to the triangle
+            return false;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = ray.origin - p0;
+        let b1 = inv_det * Vec3::dot(&s, &h);
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+        let q = Vec3::cross(&s, &edge1);
+        let b2 = inv_det * Vec3::dot(&ray.direction, &q);
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+
+        let t = inv_det * Vec3::dot(&edge2, &q);
+        if t < t_min || t_max < t {
+            return false;
+        }
+        let b0 = 1.0 - b1 - b2;
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        let outward_normal = if self.mesh.normals.is_empty() {
+            Vec3::unit_vector(&Vec3::cross(&edge1, &edge2))
+        } else {
+            let [i0, i1, i2] = self.indices;
+            Vec3::unit_vector(
+                &(b0 * &self.mesh.normals[i0]
+                    + b1 * &self.mesh.normals[i1]
+                    + b2 * &self.mesh.normals[i2]),
+            )
+        };
+        rec.set_face_normal(ray, outward_normal);
+        if self.mesh.uvs.is_empty() {
+            rec.u = b1;
+            rec.v = b2;
+        } else {
+            let [uv0, uv1, uv2] = [
+                self.mesh.uvs[self.indices[0]],
+                self.mesh.uvs[self.indices[1]],
+                self.mesh.uvs[self.indices[2]],
+            ];
+            rec.u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+            rec.v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+        }
+        rec.material = self.material.clone();
+
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        let [p0, p1, p2] = self.positions();
+        let bounds = AABB::bounding_box(&AABB::new(p0, p0), &AABB::new(p1, p1));
+        let bounds = AABB::bounding_box(&bounds, &AABB::new(p2, p2));
+        Some(bounds.padded(1e-4))
+    }
+
+    #[cfg(feature = "simd")]
+    fn triangle(&self) -> Option<[Vec3; 3]> {
+        Some(self.positions())
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        let vertices = self.positions().iter().map(vec3).collect();
+        let mut entries = vec![
+            ("type", string("triangle")),
+            ("vertices", Yaml::Array(vertices)),
+        ];
+        if !self.mesh.normals.is_empty() {
+            let normals = self
+                .indices
+                .iter()
+                .map(|idx| vec3(&self.mesh.normals[*idx]))
+                .collect();
+            entries.push(("normals", Yaml::Array(normals)));
+        }
+        if !self.mesh.uvs.is_empty() {
+            let uvs = self
+                .indices
+                .iter()
+                .map(|idx| uv(self.mesh.uvs[*idx]))
+                .collect();
+            entries.push(("uvs", Yaml::Array(uvs)));
+        }
+        entries.push(("material", writer.material(&self.material)?));
+        Ok(hash(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn new_hit_record() -> HitRecord {
+        HitRecord::new(Arc::new(Lambertian::new(Color::default())))
+    }
+
+    fn triangle() -> Triangle {
+        let mesh = MeshData {
+            positions: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ],
+            normals: vec![],
+            uvs: vec![],
+        };
+        Triangle::new(
+            Arc::new(mesh),
+            [0, 1, 2],
+            Arc::new(Lambertian::new(Color::default())),
+        )
+    }
+
+    #[test]
+    fn test_hit() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut rec = new_hit_record();
+        let ray = Ray::new(Vec3::new(0.25, 0.5, 2.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(triangle().hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng));
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.p, Vec3::new(0.25, 0.5, 0.0));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+        assert_eq!((rec.u, rec.v), (0.25, 0.5));
+
+        // From behind, the normal faces the ray
+        let ray = Ray::new(Vec3::new(0.25, 0.5, -2.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(triangle().hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn test_miss() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut rec = new_hit_record();
+        // Outside the triangle's hypotenuse
+        let ray = Ray::new(Vec3::new(0.6, 0.6, 2.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(!triangle().hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng));
+        // Parallel to the triangle's plane
+        let ray = Ray::new(Vec3::new(-1.0, 0.25, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(!triangle().hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng));
+        // Beyond t_max
+        let ray = Ray::new(Vec3::new(0.25, 0.25, 2.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(!triangle().hit(&ray, 0.001, 1.0, &mut rec, &mut rng));
+    }
+
+    #[test]
+    fn test_smooth_shading() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mesh = MeshData {
+            positions: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ],
+            normals: vec![
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ],
+            uvs: vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+        };
+        let triangle = Triangle::new(
+            Arc::new(mesh),
+            [0, 1, 2],
+            Arc::new(Lambertian::new(Color::default())),
+        );
+
+        let mut rec = new_hit_record();
+        let ray = Ray::new(Vec3::new(0.5, 0.25, 2.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(triangle.hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng));
+        let expected = Vec3::unit_vector(&Vec3::new(0.5, 0.0, 0.5));
+        assert!((rec.normal - expected).len() < 1e-12);
+        assert_eq!((rec.u, rec.v), (0.5, 0.25));
+    }
+}
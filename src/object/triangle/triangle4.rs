@@ -0,0 +1,316 @@
+use crate::aabb::AABB;
+use crate::object::{HitRecord, Object};
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::sync::Arc;
+
+/// How many triangles are tested at once, one per SIMD lane.
+const LANES: usize = 4;
+/// How many times single precision's rounding error a ray may be off by and still count as
+/// possibly hitting a triangle.
+const SLACK: f32 = 32.0 * f32::EPSILON;
+
+/// Up to four triangles tested against a ray at once in single precision. That only rules out
+/// the ones the ray clearly misses: any it might hit are tested again exactly, so the results
+/// are just the same as testing each on its own.
+pub struct Triangle4 {
+    /// The edges from each triangle's first corner, one axis at a time
+    edge1: [[f32; LANES]; 3],
+    edge2: [[f32; LANES]; 3],
+    /// How long each triangle's longer edge is, for bounding the rounding error
+    edge_length: [f32; LANES],
+    /// Kept in double precision so that rays far from small triangles find them accurately
+    corner: [Vec3; LANES],
+    triangles: Vec<Arc<dyn Object + Send + Sync>>,
+    bounding_box: AABB,
+}
+
+/// The parts of the ray the tests need, relative to each triangle's first corner. Lengths are
+/// summed over the axes, which is quicker than the real thing and never less.
+struct LaneRay {
+    direction: [f32; 3],
+    direction_length: f32,
+    offset: [[f32; LANES]; 3],
+    offset_length: [f32; LANES],
+    t_min: f32,
+    t_max: f32,
+}
+
+impl Triangle4 {
+    /// Packs between one and four objects that are all triangles. Panics otherwise.
+    pub fn new(triangles: &[Arc<dyn Object + Send + Sync>]) -> Triangle4 {
+        assert!((1..=LANES).contains(&triangles.len()));
+        let mut packet = Triangle4 {
+            edge1: [[0.0; LANES]; 3],
+            edge2: [[0.0; LANES]; 3],
+            edge_length: [0.0; LANES],
+            corner: [Vec3::origin(); LANES],
+            triangles: triangles.to_vec(),
+            bounding_box: triangles[0].bounding_box(0.0, 0.0).unwrap(),
+        };
+        for (lane, triangle) in triangles.iter().enumerate() {
+            let [p0, p1, p2] = triangle.triangle().expect("only triangles can be packed");
+            let (edge1, edge2) = (p1 - p0, p2 - p0);
+            for a in 0..=2 {
+                packet.edge1[a][lane] = edge1[a] as f32;
+                packet.edge2[a][lane] = edge2[a] as f32;
+            }
+            packet.edge_length[lane] = edge1.len().max(edge2.len()) as f32;
+            packet.corner[lane] = p0;
+            packet.bounding_box = AABB::bounding_box(
+                &packet.bounding_box,
+                &triangle.bounding_box(0.0, 0.0).unwrap(),
+            );
+        }
+        packet
+    }
+
+    fn lane_ray(&self, ray: &Ray, t_min: f64, t_max: f64) -> LaneRay {
+        let mut offset = [[0.0; LANES]; 3];
+        let mut offset_length = [0.0; LANES];
+        for (lane, corner) in self.corner.iter().enumerate() {
+            let s = ray.origin - *corner;
+            for a in 0..=2 {
+                offset[a][lane] = s[a] as f32;
+            }
+            offset_length[lane] = (s.x.abs() + s.y.abs() + s.z.abs()) as f32;
+        }
+        LaneRay {
+            direction: [
+                ray.direction.x as f32,
+                ray.direction.y as f32,
+                ray.direction.z as f32,
+            ],
+            direction_length: (ray.direction.x.abs()
+                + ray.direction.y.abs()
+                + ray.direction.z.abs()) as f32,
+            offset,
+            offset_length,
+            t_min: t_min as f32,
+            t_max: t_max as f32,
+        }
+    }
+
+    /// A bit for each triangle the ray might hit between `t_min` and `t_max`. Möller–Trumbore,
+    /// as in `Triangle`, but only ruling triangles out when the ray misses by more than the
+    /// rounding error could account for. Nearly side on ones are never ruled out.
+    #[cfg(target_arch = "x86_64")]
+    fn candidates(&self, ray: &LaneRay) -> u32 {
+        use std::arch::x86_64::*;
+
+        // SSE is part of every x86_64 processor, so these need no runtime check
+        unsafe {
+            let load = |values: &[f32; LANES]| _mm_loadu_ps(values.as_ptr());
+            let add = |a, b| _mm_add_ps(a, b);
+            let sub = |a, b| _mm_sub_ps(a, b);
+            let mul = |a, b| _mm_mul_ps(a, b);
+            let abs = |a| _mm_andnot_ps(_mm_set1_ps(-0.0), a);
+
+            let [dx, dy, dz] = ray.direction.map(|d| _mm_set1_ps(d));
+            let [e1x, e1y, e1z] = [
+                load(&self.edge1[0]),
+                load(&self.edge1[1]),
+                load(&self.edge1[2]),
+            ];
+            let [e2x, e2y, e2z] = [
+                load(&self.edge2[0]),
+                load(&self.edge2[1]),
+                load(&self.edge2[2]),
+            ];
+            let [sx, sy, sz] = [
+                load(&ray.offset[0]),
+                load(&ray.offset[1]),
+                load(&ray.offset[2]),
+            ];
+
+            let hx = sub(mul(dy, e2z), mul(dz, e2y));
+            let hy = sub(mul(dz, e2x), mul(dx, e2z));
+            let hz = sub(mul(dx, e2y), mul(dy, e2x));
+            let det = add(add(mul(e1x, hx), mul(e1y, hy)), mul(e1z, hz));
+            let inv_det = _mm_div_ps(_mm_set1_ps(1.0), det);
+
+            let qx = sub(mul(sy, e1z), mul(sz, e1y));
+            let qy = sub(mul(sz, e1x), mul(sx, e1z));
+            let qz = sub(mul(sx, e1y), mul(sy, e1x));
+            let b1 = mul(add(add(mul(sx, hx), mul(sy, hy)), mul(sz, hz)), inv_det);
+            let b2 = mul(add(add(mul(dx, qx), mul(dy, qy)), mul(dz, qz)), inv_det);
+            let t = mul(add(add(mul(e2x, qx), mul(e2y, qy)), mul(e2z, qz)), inv_det);
+
+            let edge_length = load(&self.edge_length);
+            let reach = mul(
+                mul(add(load(&ray.offset_length), edge_length), edge_length),
+                mul(abs(inv_det), _mm_set1_ps(SLACK)),
+            );
+            let slack = mul(reach, _mm_set1_ps(ray.direction_length));
+            let t_slack = add(mul(reach, edge_length), mul(abs(t), _mm_set1_ps(SLACK)));
+
+            // NaNs from side on triangles fail every comparison, so they're never ruled out
+            let missed = _mm_or_ps(
+                _mm_or_ps(
+                    _mm_cmplt_ps(b1, sub(_mm_setzero_ps(), slack)),
+                    _mm_cmplt_ps(b2, sub(_mm_setzero_ps(), slack)),
+                ),
+                _mm_or_ps(
+                    _mm_cmpgt_ps(add(b1, b2), add(_mm_set1_ps(1.0), slack)),
+                    _mm_or_ps(
+                        _mm_cmplt_ps(add(t, t_slack), _mm_set1_ps(ray.t_min)),
+                        _mm_cmpgt_ps(sub(t, t_slack), _mm_set1_ps(ray.t_max)),
+                    ),
+                ),
+            );
+            !(_mm_movemask_ps(missed) as u32) & ((1 << self.triangles.len()) - 1)
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn candidates(&self, ray: &LaneRay) -> u32 {
+        self.candidates_scalar(ray)
+    }
+
+    /// The same as `candidates` a lane at a time, for processors without SSE.
+    #[cfg(any(not(target_arch = "x86_64"), test))]
+    fn candidates_scalar(&self, ray: &LaneRay) -> u32 {
+        let [dx, dy, dz] = ray.direction;
+        let mut candidates = 0;
+        for lane in 0..self.triangles.len() {
+            let [e1x, e1y, e1z] = [0, 1, 2].map(|a| self.edge1[a][lane]);
+            let [e2x, e2y, e2z] = [0, 1, 2].map(|a| self.edge2[a][lane]);
+            let [sx, sy, sz] = [0, 1, 2].map(|a| ray.offset[a][lane]);
+
+            let hx = dy * e2z - dz * e2y;
+            let hy = dz * e2x - dx * e2z;
+            let hz = dx * e2y - dy * e2x;
+            let det = e1x * hx + e1y * hy + e1z * hz;
+            let inv_det = 1.0 / det;
+
+            let qx = sy * e1z - sz * e1y;
+            let qy = sz * e1x - sx * e1z;
+            let qz = sx * e1y - sy * e1x;
+            let b1 = (sx * hx + sy * hy + sz * hz) * inv_det;
+            let b2 = (dx * qx + dy * qy + dz * qz) * inv_det;
+            let t = (e2x * qx + e2y * qy + e2z * qz) * inv_det;
+
+            let edge_length = self.edge_length[lane];
+            let reach =
+                (ray.offset_length[lane] + edge_length) * edge_length * (inv_det.abs() * SLACK);
+            let slack = reach * ray.direction_length;
+            let t_slack = reach * edge_length + t.abs() * SLACK;
+
+            let missed = b1 < 0.0 - slack
+                || b2 < 0.0 - slack
+                || b1 + b2 > 1.0 + slack
+                || t + t_slack < ray.t_min
+                || t - t_slack > ray.t_max;
+            if !missed {
+                candidates |= 1 << lane;
+            }
+        }
+        candidates
+    }
+}
+
+impl Object for Triangle4 {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let candidates = self.candidates(&self.lane_ray(ray, t_min, t_max));
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+        for (lane, triangle) in self.triangles.iter().enumerate() {
+            if candidates & (1 << lane) != 0 && triangle.hit(ray, t_min, closest_so_far, rec, rng) {
+                hit_anything = true;
+                closest_so_far = rec.t;
+            }
+        }
+        hit_anything
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<AABB> {
+        Some(self.bounding_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::object::hit_t;
+    use crate::object::{MeshData, ObjectList, Triangle};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Packs of up to four triangles near the origin, some of them sharing edges.
+    fn packets(rng: &mut StdRng) -> Vec<Vec<Arc<dyn Object + Send + Sync>>> {
+        let material = Arc::new(Lambertian::new(Color::default()));
+        (0..200)
+            .map(|_| {
+                let positions = (0..6).map(|_| Vec3::random(rng, -2.0, 2.0)).collect();
+                let mesh = Arc::new(MeshData {
+                    positions,
+                    normals: vec![],
+                    uvs: vec![],
+                });
+                let faces = [[0, 1, 2], [1, 2, 3], [3, 4, 5], [0, 2, 5]];
+                faces[..rng.gen_range(1..=LANES)]
+                    .iter()
+                    .map(|face| -> Arc<dyn Object + Send + Sync> {
+                        Arc::new(Triangle::new(mesh.clone(), *face, material.clone()))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_triangles() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut hits = 0;
+        for triangles in packets(&mut rng) {
+            let packet = Triangle4::new(&triangles);
+            let list = ObjectList::new(triangles);
+            for _ in 0..50 {
+                // Aimed at the triangles' corners and edges as well as anywhere near them
+                let target = match rng.gen_range(0..3) {
+                    0 => Vec3::random(&mut rng, -2.0, 2.0),
+                    _ => list.objects[0].triangle().unwrap()[rng.gen_range(0..3)],
+                };
+                let origin = Vec3::random(&mut rng, -10.0, 10.0);
+                let ray = Ray::new(origin, target - origin, 0.0);
+                let expected = hit_t(&list, &ray, &mut rng);
+                assert_eq!(hit_t(&packet, &ray, &mut rng), expected);
+                hits += expected.is_some() as usize;
+            }
+        }
+        assert!(hits > 2000);
+    }
+
+    #[test]
+    fn test_scalar_matches_sse() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (mut lanes, mut candidates) = (0, 0);
+        for triangles in packets(&mut rng) {
+            lanes += 50 * triangles.len();
+            let packet = Triangle4::new(&triangles);
+            for _ in 0..50 {
+                let ray = Ray::new(
+                    Vec3::random(&mut rng, -10.0, 10.0),
+                    Vec3::random_unit_vector(&mut rng),
+                    0.0,
+                );
+                let ray = packet.lane_ray(&ray, 0.001, rng.gen_range(1.0..20.0));
+                let expected = packet.candidates_scalar(&ray);
+                assert_eq!(packet.candidates(&ray), expected);
+                candidates += expected.count_ones() as usize;
+            }
+        }
+        // Random rays mostly miss, and the test needs to notice
+        assert!(candidates * 2 < lanes);
+    }
+}
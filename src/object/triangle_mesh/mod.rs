@@ -0,0 +1,2 @@
+mod triangle_mesh;
+pub use triangle_mesh::{MeshData, TriangleMesh};
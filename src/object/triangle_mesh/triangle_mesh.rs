@@ -0,0 +1,93 @@
+use crate::aabb::AABB;
+use crate::material::Material;
+use crate::object::{HitRecord, Object, Triangle, BVH};
+use crate::ray::Ray;
+use crate::scene::{hash, string, uv, vec3, SceneWriter};
+use crate::vec3::Vec3;
+use rand::RngCore;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// Vertex buffers shared by the triangles of one or more meshes.
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    /// Per vertex normals for smooth shading, or empty to use each face's own normal.
+    pub normals: Vec<Vec3>,
+    /// Per vertex texture coordinates, or empty to use barycentric coordinates.
+    pub uvs: Vec<(f64, f64)>,
+}
+
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    faces: Vec<[usize; 3]>,
+    material: Arc<dyn Material + Send + Sync>,
+    triangles: BVH,
+}
+
+impl TriangleMesh {
+    /// Builds a mesh from faces given as indices into `mesh`. There must be at least one face.
+    pub fn new(
+        mesh: Arc<MeshData>,
+        faces: Vec<[usize; 3]>,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> TriangleMesh {
+        let triangles: Vec<Arc<dyn Object + Send + Sync>> = faces
+            .iter()
+            .map(|face| -> Arc<dyn Object + Send + Sync> {
+                Arc::new(Triangle::new(mesh.clone(), *face, material.clone()))
+            })
+            .collect();
+        let triangles = BVH::new(&triangles, 0.0, 0.0);
+
+        TriangleMesh {
+            mesh,
+            faces,
+            material,
+            triangles,
+        }
+    }
+}
+
+impl Object for TriangleMesh {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        self.triangles.hit(ray, t_min, t_max, rec, rng)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
+        self.triangles.bounding_box(time0, time1)
+    }
+
+    fn to_yaml(&self, writer: &mut SceneWriter) -> IoResult<Yaml> {
+        let mut entries = vec![
+            ("type", string("mesh")),
+            (
+                "positions",
+                Yaml::Array(self.mesh.positions.iter().map(vec3).collect()),
+            ),
+        ];
+        if !self.mesh.normals.is_empty() {
+            let normals = self.mesh.normals.iter().map(vec3).collect();
+            entries.push(("normals", Yaml::Array(normals)));
+        }
+        if !self.mesh.uvs.is_empty() {
+            let uvs = self.mesh.uvs.iter().map(|coords| uv(*coords)).collect();
+            entries.push(("uvs", Yaml::Array(uvs)));
+        }
+        let faces = self
+            .faces
+            .iter()
+            .map(|face| Yaml::Array(face.iter().map(|idx| Yaml::Integer(*idx as i64)).collect()))
+            .collect();
+        entries.push(("faces", Yaml::Array(faces)));
+        entries.push(("material", writer.material(&self.material)?));
+        Ok(hash(entries))
+    }
+}
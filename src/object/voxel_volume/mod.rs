@@ -0,0 +1,4 @@
+mod voxel_grid;
+pub use voxel_grid::VoxelGrid;
+mod voxel_volume;
+pub use voxel_volume::VoxelVolume;
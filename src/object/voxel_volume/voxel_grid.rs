@@ -0,0 +1,255 @@
+use crate::aabb::AABB;
+use crate::vec3::Vec3;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::path::Path;
+
+/// A dense grid of densities filling an axis-aligned box, with each value at the center of its
+/// voxel and linear interpolation in between.
+///
+/// On disk a grid is a short text header followed by the raw values as little endian 32 bit
+/// floats, x varying fastest, then y, then z. Header lines starting with `#` are comments.
+///
+/// ```text
+/// VOXELS
+/// # A 32 x 24 x 32 grid, 2 units across
+/// dimensions 32 24 32
+/// bounds -1 -0.75 -1 1 0.75 1
+/// data
+/// ```
+pub struct VoxelGrid {
+    pub dimensions: [usize; 3],
+    pub bounds: AABB,
+    pub data: Vec<f32>,
+    /// The highest density anywhere in the grid
+    pub max_density: f64,
+    /// Where the grid was loaded from, for writing the scene back out
+    pub file: Option<String>,
+}
+
+impl VoxelGrid {
+    /// Panics unless there's one value for each voxel.
+    pub fn new(dimensions: [usize; 3], bounds: AABB, data: Vec<f32>) -> VoxelGrid {
+        assert_eq!(data.len(), dimensions.iter().product::<usize>());
+        let max_density = data.iter().fold(0.0f32, |max, value| max.max(*value)) as f64;
+        VoxelGrid {
+            dimensions,
+            bounds,
+            data,
+            max_density,
+            file: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> IoResult<VoxelGrid> {
+        let in_file =
+            |err: IoError| IoError::new(err.kind(), format!("{}: {}", path.display(), err));
+        let mut grid = VoxelGrid::decode(&fs::read(path).map_err(in_file)?).map_err(in_file)?;
+        let path = fs::canonicalize(path).map_err(in_file)?;
+        grid.file = Some(path.to_string_lossy().to_string());
+        Ok(grid)
+    }
+
+    pub fn decode(data: &[u8]) -> IoResult<VoxelGrid> {
+        let mut lines = data.split(|byte| *byte == b'\n');
+        let mut offset = 0;
+        let mut next_line = || -> IoResult<Vec<&str>> {
+            let line = lines.next().ok_or_else(|| invalid("truncated header"))?;
+            offset += line.len() + 1;
+            let line = std::str::from_utf8(line).map_err(|_| invalid("malformed header"))?;
+            Ok(line.split_whitespace().collect())
+        };
+        if next_line()? != ["VOXELS"] {
+            return Err(invalid("not a voxel grid"));
+        }
+
+        let mut dimensions = None;
+        let mut bounds = None;
+        loop {
+            let fields = next_line()?;
+            match fields.split_first() {
+                Some((&"dimensions", values)) => {
+                    let values = parse_numbers::<usize>(values, 3, "dimensions")?;
+                    if values.contains(&0) {
+                        return Err(invalid("dimensions: a grid needs at least one voxel"));
+                    }
+                    dimensions = Some([values[0], values[1], values[2]]);
+                }
+                Some((&"bounds", values)) => {
+                    let v = parse_numbers::<f64>(values, 6, "bounds")?;
+                    if v[0] >= v[3] || v[1] >= v[4] || v[2] >= v[5] {
+                        return Err(invalid("bounds: the minimum has to be below the maximum"));
+                    }
+                    bounds = Some(AABB::new(
+                        Vec3::new(v[0], v[1], v[2]),
+                        Vec3::new(v[3], v[4], v[5]),
+                    ));
+                }
+                Some((&"data", [])) => break,
+                Some((comment, _)) if comment.starts_with('#') => continue,
+                Some((other, _)) => {
+                    return Err(invalid(&format!("unknown header field \"{}\"", other)))
+                }
+                None => continue,
+            }
+        }
+        let dimensions = dimensions.ok_or_else(|| invalid("missing dimensions"))?;
+        let bounds = bounds.ok_or_else(|| invalid("missing bounds"))?;
+
+        // The header can claim any size, so check it fits before working out where data ends
+        let end = dimensions
+            .iter()
+            .try_fold(4usize, |size, n| size.checked_mul(*n))
+            .and_then(|size| size.checked_add(offset))
+            .ok_or_else(|| invalid("dimensions too large"))?;
+        let raw = data
+            .get(offset..end)
+            .ok_or_else(|| invalid("truncated data"))?;
+        let values = raw
+            .chunks(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect::<Vec<f32>>();
+        if values
+            .iter()
+            .any(|value| !(*value >= 0.0 && value.is_finite()))
+        {
+            return Err(invalid("densities have to be finite and 0 or more"));
+        }
+        Ok(VoxelGrid::new(dimensions, bounds, values))
+    }
+
+    /// The density at `p`, or 0 outside the grid's bounds.
+    pub fn density(&self, p: &Vec3) -> f64 {
+        let (min, max) = (self.bounds.minimum, self.bounds.maximum);
+        if (0..3).any(|a| p[a] < min[a] || p[a] > max[a]) {
+            return 0.0;
+        }
+
+        // Find the voxel centers either side of p along each axis, and how far it is between them
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut weight = [0.0; 3];
+        for a in 0..3 {
+            let n = self.dimensions[a];
+            let x =
+                ((p[a] - min[a]) / (max[a] - min[a]) * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            lower[a] = x as usize;
+            upper[a] = (lower[a] + 1).min(n - 1);
+            weight[a] = x - lower[a] as f64;
+        }
+
+        let [nx, ny, _] = self.dimensions;
+        let mut density = 0.0;
+        for corner in 0..8 {
+            let mut index = 0;
+            let mut corner_weight = 1.0;
+            for (a, stride) in [1, nx, nx * ny].iter().enumerate() {
+                let (i, w) = if corner & (1 << a) == 0 {
+                    (lower[a], 1.0 - weight[a])
+                } else {
+                    (upper[a], weight[a])
+                };
+                index += i * stride;
+                corner_weight *= w;
+            }
+            density += corner_weight * self.data[index] as f64;
+        }
+        density
+    }
+}
+
+fn parse_numbers<T: std::str::FromStr>(
+    values: &[&str],
+    count: usize,
+    field: &str,
+) -> IoResult<Vec<T>> {
+    if values.len() != count {
+        return Err(invalid(&format!("{}: expected {} numbers", field, count)));
+    }
+    values
+        .iter()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| invalid(&format!("{}: malformed number \"{}\"", field, value)))
+        })
+        .collect()
+}
+
+fn invalid(message: &str) -> IoError {
+    IoError::new(IoErrorKind::InvalidData, format!("voxel grid: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(grid: &VoxelGrid) -> Vec<u8> {
+        let (min, max) = (grid.bounds.minimum, grid.bounds.maximum);
+        let mut out = format!(
+            "VOXELS\ndimensions {} {} {}\nbounds {} {} {} {} {} {}\ndata\n",
+            grid.dimensions[0],
+            grid.dimensions[1],
+            grid.dimensions[2],
+            min.x,
+            min.y,
+            min.z,
+            max.x,
+            max.y,
+            max.z
+        )
+        .into_bytes();
+        for value in &grid.data {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out
+    }
+
+    fn grid() -> VoxelGrid {
+        // Two voxels wide, one deep and high, between x = 0 and 4
+        VoxelGrid::new(
+            [2, 1, 1],
+            AABB::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(4.0, 1.0, 1.0)),
+            vec![1.0, 3.0],
+        )
+    }
+
+    #[test]
+    fn test_density() {
+        let grid = grid();
+        assert_eq!(grid.max_density, 3.0);
+        let density = |x: f64| grid.density(&Vec3::new(x, 0.5, 0.5));
+        // Flat out to the voxel centers at x = 1 and 3, and linear in between
+        assert_eq!(density(0.0), 1.0);
+        assert_eq!(density(1.0), 1.0);
+        assert_eq!(density(2.0), 2.0);
+        assert_eq!(density(2.5), 2.5);
+        assert_eq!(density(4.0), 3.0);
+        assert_eq!(density(4.5), 0.0);
+    }
+
+    #[test]
+    fn test_decode() {
+        let decoded = VoxelGrid::decode(&encode(&grid())).unwrap();
+        assert_eq!(decoded.dimensions, [2, 1, 1]);
+        assert_eq!(decoded.bounds.maximum, Vec3::new(4.0, 1.0, 1.0));
+        assert_eq!(decoded.data, vec![1.0, 3.0]);
+
+        let error = |data: &[u8]| VoxelGrid::decode(data).err().unwrap().to_string();
+        let mut truncated = encode(&grid());
+        truncated.pop();
+        assert_eq!(error(&truncated), "voxel grid: truncated data");
+        assert_eq!(
+            error(b"VOXELS\ndimensions 2 1\n"),
+            "voxel grid: dimensions: expected 3 numbers"
+        );
+        assert_eq!(
+            error(b"VOXELS\ndimensions 4294967296 4294967296 2\nbounds 0 0 0 1 1 1\ndata\n"),
+            "voxel grid: dimensions too large"
+        );
+        assert_eq!(
+            error(b"VOXELS\ndimensions 1 1 1\nbounds 0 0 0 1 1 1\ndata\n\x00\x00\x80\xbf"),
+            "voxel grid: densities have to be finite and 0 or more"
+        );
+    }
+}
@@ -0,0 +1,1095 @@
+use crate::background::{Background, Constant, Environment, Gradient};
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::matrix::{Matrix, Transform};
+use crate::obj::load_obj;
+use crate::object::{
+    BoxShape, ConstantMedium, Fog, Instance, MeshData, MovingSphere, Object, ObjectList, Quad,
+    Sphere, Triangle, TriangleMesh, VoxelGrid, VoxelVolume, DEFAULT_FOG_EXTENT,
+};
+use crate::scene::Scene;
+use crate::texture::{Checker, ImageTexture, Marble, Noise, SolidColor, Texture, Wood};
+use crate::vec3::Vec3;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Loads a YAML scene description from `path`, returning the camera it describes
+/// (built for the given aspect ratio) and the scene itself.
+pub fn load_scene(path: &str, aspect_ratio: f64) -> IoResult<(Camera, Scene)> {
+    let source = fs::read_to_string(path)
+        .map_err(|err| IoError::new(err.kind(), format!("{}: {}", path, err)))?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    parse_scene(&source, dir, aspect_ratio)
+        .map_err(|err| IoError::new(err.kind(), format!("{}: {}", path, err)))
+}
+
+/// Parses a scene description, resolving any files it refers to relative to `dir`.
+pub fn parse_scene(source: &str, dir: &Path, aspect_ratio: f64) -> IoResult<(Camera, Scene)> {
+    let docs = YamlLoader::load_from_str(source).map_err(|err| invalid(err.to_string()))?;
+    let doc = match docs.first() {
+        Some(doc) => doc,
+        None => return Err(invalid("scene file is empty".to_string())),
+    };
+
+    let camera = parse_camera(&doc["camera"], aspect_ratio)?;
+
+    let mut loader = SceneLoader {
+        dir,
+        materials: HashMap::new(),
+        models: HashMap::new(),
+        obj_models: RefCell::new(HashMap::new()),
+    };
+    loader.parse_materials(&doc["materials"])?;
+    loader.parse_models(&doc["models"])?;
+    let mut objects = loader.parse_objects(&doc["objects"], "objects")?;
+
+    let mut scene = Scene::new(&mut objects);
+    if !doc["background"].is_badvalue() {
+        scene.background = parse_background(&doc["background"], "background", dir)?;
+    }
+    if !doc["fog"].is_badvalue() {
+        scene.fog = Some(parse_fog(&doc["fog"], "fog")?);
+    }
+    Ok((camera, scene))
+}
+
+/// An OBJ file, and the name of the material used instead of its own if there is one.
+type ObjModelKey = (PathBuf, Option<String>);
+
+struct SceneLoader<'a> {
+    dir: &'a Path,
+    materials: HashMap<String, Arc<dyn Material + Send + Sync>>,
+    /// Objects that instances can share by name
+    models: HashMap<String, Arc<dyn Object + Send + Sync>>,
+    /// OBJ models already loaded, so that every copy of one shares its triangles
+    obj_models: RefCell<HashMap<ObjModelKey, Arc<dyn Object + Send + Sync>>>,
+}
+
+impl SceneLoader<'_> {
+    fn parse_materials(&mut self, node: &Yaml) -> IoResult<()> {
+        let materials = match node {
+            Yaml::Hash(materials) => materials,
+            Yaml::BadValue | Yaml::Null => return Ok(()),
+            _ => {
+                return Err(invalid(
+                    "materials: expected a mapping of names to materials".to_string(),
+                ))
+            }
+        };
+
+        for (name, material) in materials {
+            let name = match name.as_str() {
+                Some(name) => name,
+                None => return Err(invalid("materials: names must be strings".to_string())),
+            };
+            let context = format!("materials.{}", name);
+            let material = parse_material(material, &context, self.dir)?;
+            self.materials.insert(name.to_string(), material);
+        }
+        Ok(())
+    }
+
+    fn parse_models(&mut self, node: &Yaml) -> IoResult<()> {
+        let models = match node {
+            Yaml::Hash(models) => models,
+            Yaml::BadValue | Yaml::Null => return Ok(()),
+            _ => {
+                return Err(invalid(
+                    "models: expected a mapping of names to objects".to_string(),
+                ))
+            }
+        };
+
+        // Later models can be built from earlier ones
+        for (name, model) in models {
+            let name = match name.as_str() {
+                Some(name) => name,
+                None => return Err(invalid("models: names must be strings".to_string())),
+            };
+            let context = format!("models.{}", name);
+            let model = self.parse_object(model, &context)?;
+            self.models.insert(name.to_string(), model);
+        }
+        Ok(())
+    }
+
+    fn parse_objects(&self, node: &Yaml, context: &str) -> IoResult<ObjectList> {
+        let objects = match node {
+            Yaml::Array(objects) => objects,
+            Yaml::BadValue => return Err(invalid(format!("missing {}", context))),
+            _ => return Err(invalid(format!("{}: expected a list", context))),
+        };
+        if objects.is_empty() {
+            return Err(invalid(format!(
+                "{}: expected at least one object",
+                context
+            )));
+        }
+
+        let mut scene_objects = vec![];
+        for (idx, object) in objects.iter().enumerate() {
+            let context = format!("{}[{}]", context, idx);
+            scene_objects.push(self.parse_object(object, &context)?);
+        }
+        Ok(ObjectList::new(scene_objects))
+    }
+
+    fn parse_object(&self, node: &Yaml, context: &str) -> IoResult<Arc<dyn Object + Send + Sync>> {
+        match get_str(node, "type", context)? {
+            "sphere" => Ok(Arc::new(Sphere::new(
+                get_vec3(node, "center", context)?,
+                get_f64(node, "radius", context)?,
+                self.get_material(node, context)?,
+            ))),
+            "moving_sphere" => Ok(Arc::new(MovingSphere::new(
+                get_vec3(node, "center0", context)?,
+                get_vec3(node, "center1", context)?,
+                get_optional(node, "time0", context, parse_f64)?.unwrap_or(0.0),
+                get_optional(node, "time1", context, parse_f64)?.unwrap_or(1.0),
+                get_f64(node, "radius", context)?,
+                self.get_material(node, context)?,
+            ))),
+            "quad" => Ok(Arc::new(Quad::new(
+                get_vec3(node, "corner", context)?,
+                get_vec3(node, "u", context)?,
+                get_vec3(node, "v", context)?,
+                self.get_material(node, context)?,
+            ))),
+            "box" => Ok(Arc::new(BoxShape::new(
+                get_vec3(node, "min", context)?,
+                get_vec3(node, "max", context)?,
+                self.get_material(node, context)?,
+            ))),
+            "triangle" => Ok(Arc::new(self.parse_triangle(node, context)?)),
+            "mesh" => Ok(Arc::new(self.parse_mesh(node, context)?)),
+            "obj" => self.parse_obj(node, context),
+            "instance" => Ok(Arc::new(Instance::new(
+                get_required(node, "object", context, |node, context| {
+                    self.parse_instanced(node, context)
+                })?,
+                get_required(node, "transform", context, parse_transform)?,
+            ))),
+            "constant_medium" => Ok(Arc::new(ConstantMedium::new(
+                get_required(node, "boundary", context, |node, context| {
+                    self.parse_object(node, context)
+                })?,
+                get_required(node, "density", context, parse_positive)?,
+                self.get_material(node, context)?,
+            ))),
+            "voxel_volume" => Ok(Arc::new(self.parse_voxel_volume(node, context)?)),
+            "list" => Ok(Arc::new(
+                self.parse_objects(&node["objects"], &format!("{}.objects", context))?,
+            )),
+            other => Err(invalid(format!(
+                "{}.type: unknown object type \"{}\"",
+                context, other
+            ))),
+        }
+    }
+
+    fn parse_triangle(&self, node: &Yaml, context: &str) -> IoResult<Triangle> {
+        let per_vertex = |key: &str| {
+            let values = get_optional(node, key, context, |node, context| {
+                parse_list(node, context, parse_vec3)
+            })?;
+            match values {
+                Some(values) if values.len() != 3 => Err(invalid(format!(
+                    "{}.{}: expected three entries, one per vertex",
+                    context, key
+                ))),
+                values => Ok(values.unwrap_or_default()),
+            }
+        };
+        let positions = per_vertex("vertices")?;
+        if positions.is_empty() {
+            return Err(invalid(format!("{}: missing vertices", context)));
+        }
+        let normals = per_vertex("normals")?;
+        let uvs = get_optional(node, "uvs", context, |node, context| {
+            parse_list(node, context, parse_uv)
+        })?
+        .unwrap_or_default();
+        if !uvs.is_empty() && uvs.len() != 3 {
+            return Err(invalid(format!(
+                "{}.uvs: expected three entries, one per vertex",
+                context
+            )));
+        }
+
+        let mesh = MeshData {
+            positions,
+            normals,
+            uvs,
+        };
+        Ok(Triangle::new(
+            Arc::new(mesh),
+            [0, 1, 2],
+            self.get_material(node, context)?,
+        ))
+    }
+
+    fn parse_mesh(&self, node: &Yaml, context: &str) -> IoResult<TriangleMesh> {
+        let positions = get_required(node, "positions", context, |node, context| {
+            parse_list(node, context, parse_vec3)
+        })?;
+        let normals = get_optional(node, "normals", context, |node, context| {
+            parse_list(node, context, parse_vec3)
+        })?
+        .unwrap_or_default();
+        let uvs = get_optional(node, "uvs", context, |node, context| {
+            parse_list(node, context, parse_uv)
+        })?
+        .unwrap_or_default();
+        for (key, len) in &[("normals", normals.len()), ("uvs", uvs.len())] {
+            if *len != 0 && *len != positions.len() {
+                return Err(invalid(format!(
+                    "{}.{}: expected one entry per position ({} given for {} positions)",
+                    context,
+                    key,
+                    len,
+                    positions.len()
+                )));
+            }
+        }
+
+        let faces = get_required(node, "faces", context, |node, context| {
+            parse_list(node, context, parse_face)
+        })?;
+        if faces.is_empty() {
+            return Err(invalid(format!(
+                "{}.faces: expected at least one face",
+                context
+            )));
+        }
+        for (idx, face) in faces.iter().enumerate() {
+            if let Some(vertex) = face.iter().find(|vertex| **vertex >= positions.len()) {
+                return Err(invalid(format!(
+                    "{}.faces[{}]: vertex index {} out of range ({} positions)",
+                    context,
+                    idx,
+                    vertex,
+                    positions.len()
+                )));
+            }
+        }
+
+        let mesh = MeshData {
+            positions,
+            normals,
+            uvs,
+        };
+        Ok(TriangleMesh::new(
+            Arc::new(mesh),
+            faces,
+            self.get_material(node, context)?,
+        ))
+    }
+
+    /// An instance's object: either the name of one of the scene's models or a description of
+    /// its own.
+    fn parse_instanced(
+        &self,
+        node: &Yaml,
+        context: &str,
+    ) -> IoResult<Arc<dyn Object + Send + Sync>> {
+        match node.as_str() {
+            Some(name) => match self.models.get(name) {
+                Some(model) => Ok(model.clone()),
+                None => Err(invalid(format!("{}: unknown model \"{}\"", context, name))),
+            },
+            None => self.parse_object(node, context),
+        }
+    }
+
+    /// Loads an OBJ model, or reuses the copy already loaded, and places it with an instance
+    /// if it's transformed.
+    fn parse_obj(&self, node: &Yaml, context: &str) -> IoResult<Arc<dyn Object + Send + Sync>> {
+        let path = self.dir.join(get_str(node, "file", context)?);
+        let transform = get_optional(node, "transform", context, parse_transform)?;
+        let (material, material_name) = match node["material"] {
+            Yaml::BadValue => (None, None),
+            _ => (
+                Some(self.get_material(node, context)?),
+                Some(get_str(node, "material", context)?.to_string()),
+            ),
+        };
+
+        let key = (
+            fs::canonicalize(&path).unwrap_or_else(|_| path.clone()),
+            material_name,
+        );
+        let cached = self.obj_models.borrow().get(&key).cloned();
+        let model = match cached {
+            Some(model) => model,
+            None => {
+                let model: Arc<dyn Object + Send + Sync> = Arc::new(
+                    load_obj(&path, &Matrix::identity(), material).map_err(|err| {
+                        IoError::new(err.kind(), format!("{}.file: {}", context, err))
+                    })?,
+                );
+                self.obj_models.borrow_mut().insert(key, model.clone());
+                model
+            }
+        };
+
+        Ok(match transform {
+            Some(transform) => Arc::new(Instance::new(model, transform)),
+            None => model,
+        })
+    }
+
+    fn parse_voxel_volume(&self, node: &Yaml, context: &str) -> IoResult<VoxelVolume> {
+        let file = get_str(node, "file", context)?;
+        let grid = VoxelGrid::load(&self.dir.join(file))
+            .map_err(|err| IoError::new(err.kind(), format!("{}.file: {}", context, err)))?;
+        Ok(VoxelVolume::new(
+            Arc::new(grid),
+            get_optional(node, "density", context, parse_positive)?.unwrap_or(1.0),
+            self.get_material(node, context)?,
+        ))
+    }
+
+    fn get_material(
+        &self,
+        node: &Yaml,
+        context: &str,
+    ) -> IoResult<Arc<dyn Material + Send + Sync>> {
+        let name = get_str(node, "material", context)?;
+        match self.materials.get(name) {
+            Some(material) => Ok(material.clone()),
+            None => Err(invalid(format!(
+                "{}.material: unknown material \"{}\"",
+                context, name
+            ))),
+        }
+    }
+}
+
+fn parse_camera(node: &Yaml, aspect_ratio: f64) -> IoResult<Camera> {
+    let context = "camera";
+    if node.is_badvalue() {
+        return Err(invalid("missing camera".to_string()));
+    }
+
+    let look_from = get_vec3(node, "look_from", context)?;
+    let look_at = get_vec3(node, "look_at", context)?;
+    let vup = get_optional(node, "vup", context, parse_vec3)?.unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+    let vertical_fov = get_f64(node, "vertical_fov", context)?;
+    let aperture = get_optional(node, "aperture", context, parse_f64)?.unwrap_or(0.0);
+    let focus_dist = get_optional(node, "focus_dist", context, parse_f64)?
+        .unwrap_or_else(|| (look_from - look_at).len());
+
+    let mut camera = Camera::new(
+        &look_from,
+        &look_at,
+        &vup,
+        vertical_fov,
+        aspect_ratio,
+        aperture,
+        focus_dist,
+    );
+    camera.time0 = get_optional(node, "time0", context, parse_f64)?.unwrap_or(0.0);
+    camera.time1 = get_optional(node, "time1", context, parse_f64)?.unwrap_or(camera.time0);
+    if camera.time1 < camera.time0 {
+        return Err(invalid(
+            "camera.time1: the shutter can't close before it opens".to_string(),
+        ));
+    }
+    Ok(camera)
+}
+
+/// Parses a background, which can be given as just a color as a shorthand for a constant one.
+fn parse_background(
+    node: &Yaml,
+    context: &str,
+    dir: &Path,
+) -> IoResult<Box<dyn Background + Send + Sync>> {
+    if let Yaml::Array(_) = node {
+        return Ok(Box::new(Constant::new(parse_color(node, context)?)));
+    }
+    match get_str(node, "type", context)? {
+        "constant" => Ok(Box::new(Constant::new(get_color(node, "color", context)?))),
+        "gradient" => Ok(Box::new(Gradient::new(
+            get_color(node, "bottom", context)?,
+            get_color(node, "top", context)?,
+        ))),
+        "environment" => {
+            let file = get_str(node, "file", context)?;
+            let rotation = get_optional(node, "rotation", context, parse_f64)?.unwrap_or(0.0);
+            let intensity = get_optional(node, "intensity", context, parse_f64)?.unwrap_or(1.0);
+            let environment = Environment::load(&dir.join(file), rotation, intensity)
+                .map_err(|err| IoError::new(err.kind(), format!("{}.file: {}", context, err)))?;
+            Ok(Box::new(environment))
+        }
+        other => Err(invalid(format!(
+            "{}.type: unknown background type \"{}\"",
+            context, other
+        ))),
+    }
+}
+
+/// Parses a transform, given either as a mapping with any of `scale`, `rotate` and `translate`,
+/// or as the top three rows of a 4x4 matrix.
+fn parse_transform(node: &Yaml, context: &str) -> IoResult<Matrix> {
+    let matrix = match node {
+        Yaml::Hash(_) => parse_transform_steps(node, context)?.matrix(),
+        Yaml::Array(rows) if rows.len() == 3 => {
+            let mut matrix = Matrix::identity();
+            for (idx, (row, values)) in rows.iter().zip(matrix.rows.iter_mut()).enumerate() {
+                let context = format!("{}[{}]", context, idx);
+                match row.as_vec().map(Vec::as_slice) {
+                    Some(row) if row.len() == 4 => {
+                        for (value, node) in values.iter_mut().zip(row) {
+                            *value = parse_f64(node, &context)?;
+                        }
+                    }
+                    _ => return Err(invalid(format!("{}: expected four numbers", context))),
+                }
+            }
+            matrix
+        }
+        _ => {
+            return Err(invalid(format!(
+                "{}: expected a mapping with any of scale, rotate and translate, or three rows of a matrix",
+                context
+            )))
+        }
+    };
+    if matrix.inverse().is_none() {
+        return Err(invalid(format!(
+            "{}: flattens everything, so it can't be undone",
+            context
+        )));
+    }
+    Ok(matrix)
+}
+
+fn parse_transform_steps(node: &Yaml, context: &str) -> IoResult<Transform> {
+    let mut transform = Transform::default();
+    // A single number scales uniformly
+    if let Some(scale) = get_optional(node, "scale", context, |node, context| match node {
+        Yaml::Array(_) => parse_vec3(node, context),
+        _ => parse_f64(node, context).map(|scale| Vec3::new(scale, scale, scale)),
+    })? {
+        transform.scale = scale;
+    }
+    if let Some(rotate) = get_optional(node, "rotate", context, parse_vec3)? {
+        transform.rotate = rotate;
+    }
+    if let Some(translate) = get_optional(node, "translate", context, parse_vec3)? {
+        transform.translate = translate;
+    }
+    Ok(transform)
+}
+
+fn parse_material(
+    node: &Yaml,
+    context: &str,
+    dir: &Path,
+) -> IoResult<Arc<dyn Material + Send + Sync>> {
+    let get_texture = |key: &str| {
+        get_required(node, key, context, |node, context| {
+            parse_texture(node, context, dir)
+        })
+    };
+    match get_str(node, "type", context)? {
+        "lambertian" => Ok(Arc::new(Lambertian::textured(get_texture("albedo")?))),
+        "metal" => Ok(Arc::new(Metal::textured(
+            get_texture("albedo")?,
+            get_optional(node, "fuzz", context, parse_f64)?.unwrap_or(0.0),
+        ))),
+        "dielectric" => Ok(Arc::new(Dielectric::new(get_f64(
+            node,
+            "refraction_index",
+            context,
+        )?))),
+        "isotropic" => Ok(Arc::new(Isotropic::textured(get_texture("albedo")?))),
+        "diffuse_light" => Ok(Arc::new(DiffuseLight::new(get_color(
+            node, "emit", context,
+        )?))),
+        other => Err(invalid(format!(
+            "{}.type: unknown material type \"{}\"",
+            context, other
+        ))),
+    }
+}
+
+/// Parses a texture, which can be given as just a color as a shorthand for a solid one.
+fn parse_texture(
+    node: &Yaml,
+    context: &str,
+    dir: &Path,
+) -> IoResult<Arc<dyn Texture + Send + Sync>> {
+    match node {
+        Yaml::Array(_) => return Ok(Arc::new(SolidColor::new(parse_color(node, context)?))),
+        Yaml::Hash(_) => {}
+        _ => {
+            return Err(invalid(format!(
+                "{}: expected a color, e.g. [0.5, 0.5, 0.5], or a texture",
+                context
+            )))
+        }
+    }
+    let get_texture = |key: &str| {
+        get_required(node, key, context, |node, context| {
+            parse_texture(node, context, dir)
+        })
+    };
+    match get_str(node, "type", context)? {
+        "checker" => Ok(Arc::new(Checker::new(
+            get_optional(node, "size", context, parse_f64)?.unwrap_or(1.0),
+            get_texture("even")?,
+            get_texture("odd")?,
+        ))),
+        "noise" | "marble" | "wood" => parse_procedural(node, context),
+        "image" => {
+            let file = get_str(node, "file", context)?;
+            let texture = ImageTexture::load(&dir.join(file))
+                .map_err(|err| IoError::new(err.kind(), format!("{}.file: {}", context, err)))?;
+            Ok(Arc::new(texture))
+        }
+        other => Err(invalid(format!(
+            "{}.type: unknown texture type \"{}\"",
+            context, other
+        ))),
+    }
+}
+
+/// Parses one of the textures built on Perlin noise, which share most of their settings.
+fn parse_procedural(node: &Yaml, context: &str) -> IoResult<Arc<dyn Texture + Send + Sync>> {
+    let kind = get_str(node, "type", context)?;
+    let (default_turbulence, default_dark, default_light) = match kind {
+        "noise" => (0.0, Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)),
+        "marble" => (10.0, Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)),
+        _ => (
+            1.0,
+            Color::new(0.45, 0.27, 0.12),
+            Color::new(0.76, 0.55, 0.33),
+        ),
+    };
+    let seed = get_optional(node, "seed", context, parse_integer)?.unwrap_or(0);
+    let scale = get_optional(node, "scale", context, parse_f64)?.unwrap_or(1.0);
+    let octaves = get_optional(node, "octaves", context, parse_integer)?.unwrap_or(7) as u32;
+    let turbulence =
+        get_optional(node, "turbulence", context, parse_f64)?.unwrap_or(default_turbulence);
+    let dark = get_optional(node, "dark", context, parse_color)?.unwrap_or(default_dark);
+    let light = get_optional(node, "light", context, parse_color)?.unwrap_or(default_light);
+
+    Ok(match kind {
+        "noise" => Arc::new(Noise::new(seed, scale, octaves, dark, light)),
+        "marble" => Arc::new(Marble::new(seed, scale, octaves, turbulence, dark, light)),
+        _ => Arc::new(Wood::new(seed, scale, octaves, turbulence, dark, light)),
+    })
+}
+
+fn invalid(message: String) -> IoError {
+    IoError::new(IoErrorKind::InvalidData, message)
+}
+
+fn get_optional<T>(
+    node: &Yaml,
+    key: &str,
+    context: &str,
+    parse: impl Fn(&Yaml, &str) -> IoResult<T>,
+) -> IoResult<Option<T>> {
+    match &node[key] {
+        Yaml::BadValue => Ok(None),
+        value => parse(value, &format!("{}.{}", context, key)).map(Some),
+    }
+}
+
+fn get_required<T>(
+    node: &Yaml,
+    key: &str,
+    context: &str,
+    parse: impl Fn(&Yaml, &str) -> IoResult<T>,
+) -> IoResult<T> {
+    match get_optional(node, key, context, parse)? {
+        Some(value) => Ok(value),
+        None => Err(invalid(format!("{}: missing {}", context, key))),
+    }
+}
+
+fn get_str<'a>(node: &'a Yaml, key: &str, context: &str) -> IoResult<&'a str> {
+    match &node[key] {
+        Yaml::String(value) => Ok(value),
+        Yaml::BadValue => Err(invalid(format!("{}: missing {}", context, key))),
+        _ => Err(invalid(format!("{}.{}: expected a string", context, key))),
+    }
+}
+
+fn get_f64(node: &Yaml, key: &str, context: &str) -> IoResult<f64> {
+    get_required(node, key, context, parse_f64)
+}
+
+fn get_vec3(node: &Yaml, key: &str, context: &str) -> IoResult<Vec3> {
+    get_required(node, key, context, parse_vec3)
+}
+
+fn get_color(node: &Yaml, key: &str, context: &str) -> IoResult<Color> {
+    get_required(node, key, context, parse_color)
+}
+
+fn parse_f64(node: &Yaml, context: &str) -> IoResult<f64> {
+    match node {
+        Yaml::Integer(value) => Ok(*value as f64),
+        Yaml::Real(_) => node
+            .as_f64()
+            .ok_or_else(|| invalid(format!("{}: malformed number", context))),
+        _ => Err(invalid(format!("{}: expected a number", context))),
+    }
+}
+
+fn parse_fog(node: &Yaml, context: &str) -> IoResult<Fog> {
+    Ok(Fog::new(
+        get_required(node, "density", context, parse_positive)?,
+        get_optional(node, "color", context, parse_color)?.unwrap_or(Color::new(1.0, 1.0, 1.0)),
+        get_optional(node, "extent", context, parse_positive)?.unwrap_or(DEFAULT_FOG_EXTENT),
+    ))
+}
+
+fn parse_positive(node: &Yaml, context: &str) -> IoResult<f64> {
+    match parse_f64(node, context)? {
+        density if density > 0.0 => Ok(density),
+        _ => Err(invalid(format!("{}: expected a number above 0", context))),
+    }
+}
+
+fn parse_integer(node: &Yaml, context: &str) -> IoResult<u64> {
+    match node {
+        Yaml::Integer(value) if *value >= 0 => Ok(*value as u64),
+        _ => Err(invalid(format!(
+            "{}: expected a whole number, 0 or more",
+            context
+        ))),
+    }
+}
+
+fn parse_vec3(node: &Yaml, context: &str) -> IoResult<Vec3> {
+    let malformed = || {
+        invalid(format!(
+            "{}: expected a list of three numbers, e.g. [0.0, 1.0, 0.0]",
+            context
+        ))
+    };
+    match node {
+        Yaml::Array(components) if components.len() == 3 => {
+            let mut xyz = [0.0; 3];
+            for (component, value) in xyz.iter_mut().zip(components) {
+                *component = parse_f64(value, context).map_err(|_| malformed())?;
+            }
+            Ok(Vec3::new(xyz[0], xyz[1], xyz[2]))
+        }
+        _ => Err(malformed()),
+    }
+}
+
+fn parse_color(node: &Yaml, context: &str) -> IoResult<Color> {
+    let v = parse_vec3(node, context)?;
+    Ok(Color::new(v.x, v.y, v.z))
+}
+
+fn parse_list<T>(
+    node: &Yaml,
+    context: &str,
+    parse: impl Fn(&Yaml, &str) -> IoResult<T>,
+) -> IoResult<Vec<T>> {
+    match node {
+        Yaml::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| parse(item, &format!("{}[{}]", context, idx)))
+            .collect(),
+        _ => Err(invalid(format!("{}: expected a list", context))),
+    }
+}
+
+fn parse_uv(node: &Yaml, context: &str) -> IoResult<(f64, f64)> {
+    let malformed = || {
+        invalid(format!(
+            "{}: expected a list of two numbers, e.g. [0.5, 1.0]",
+            context
+        ))
+    };
+    match node {
+        Yaml::Array(components) if components.len() == 2 => Ok((
+            parse_f64(&components[0], context).map_err(|_| malformed())?,
+            parse_f64(&components[1], context).map_err(|_| malformed())?,
+        )),
+        _ => Err(malformed()),
+    }
+}
+
+fn parse_face(node: &Yaml, context: &str) -> IoResult<[usize; 3]> {
+    let malformed = || {
+        invalid(format!(
+            "{}: expected a list of three vertex indices, e.g. [0, 1, 2]",
+            context
+        ))
+    };
+    match node {
+        Yaml::Array(indices) if indices.len() == 3 => {
+            let mut face = [0; 3];
+            for (vertex, index) in face.iter_mut().zip(indices) {
+                *vertex = match index {
+                    Yaml::Integer(index) if *index >= 0 => *index as usize,
+                    _ => return Err(malformed()),
+                };
+            }
+            Ok(face)
+        }
+        _ => Err(malformed()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+    use crate::scene::writer::dump_scene;
+
+    const SCENE: &str = "
+camera:
+  look_from: [0, 0, 5]
+  look_at: [0, 0, 0]
+  vertical_fov: 40
+materials:
+  red:
+    type: lambertian
+    albedo: [0.8, 0.1, 0.1]
+  glass:
+    type: dielectric
+    refraction_index: 1.5
+objects:
+  - type: sphere
+    center: [0, 0, 0]
+    radius: 1
+    material: red
+  - type: sphere
+    center: [2.5, 0, 0]
+    radius: 0.5
+    material: glass
+";
+
+    fn error_message(source: &str) -> String {
+        match parse_scene(source, Path::new("."), 1.0) {
+            Ok(_) => panic!("expected scene to be rejected"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let (_camera, scene) = parse_scene(SCENE, Path::new("."), 1.0).unwrap();
+        let bounds = scene.bounding_box(0.0, 0.0).unwrap();
+        assert_eq!(bounds.minimum, Vec3::new(-1.0, -1.0, -1.0));
+        assert_eq!(bounds.maximum, Vec3::new(3.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_unknown_material() {
+        let source = SCENE.replace("material: glass", "material: glas");
+        assert_eq!(
+            error_message(&source),
+            "objects[1].material: unknown material \"glas\""
+        );
+    }
+
+    #[test]
+    fn test_malformed_vector() {
+        let source = SCENE.replace("[2.5, 0, 0]", "[2.5, 0]");
+        assert_eq!(
+            error_message(&source),
+            "objects[1].center: expected a list of three numbers, e.g. [0.0, 1.0, 0.0]"
+        );
+        let source = SCENE.replace("[0.8, 0.1, 0.1]", "[0.8, red, 0.1]");
+        assert_eq!(
+            error_message(&source),
+            "materials.red.albedo: expected a list of three numbers, e.g. [0.0, 1.0, 0.0]"
+        );
+    }
+
+    #[test]
+    fn test_mesh() {
+        let mesh = "
+  - type: mesh
+    positions: [[0, 0, 0], [1, 0, 0], [0, 1, 0], [0, 0, 1]]
+    faces: [[0, 1, 2], [0, 2, 3], [0, 3, 1], [1, 3, 2]]
+    material: red
+";
+        let (_camera, scene) =
+            parse_scene(&format!("{}{}", SCENE, mesh), Path::new("."), 1.0).unwrap();
+        let bounds = scene.bounding_box(0.0, 0.0).unwrap();
+        assert_eq!(bounds.minimum, Vec3::new(-1.0, -1.0, -1.0));
+
+        let source = format!("{}{}", SCENE, mesh.replace("[1, 3, 2]", "[1, 3, 4]"));
+        assert_eq!(
+            error_message(&source),
+            "objects[2].faces[3]: vertex index 4 out of range (4 positions)"
+        );
+    }
+
+    #[test]
+    fn test_quads() {
+        let shapes = "
+  - type: quad
+    corner: [-2, -3, 0]
+    u: [1, 0, 0]
+    v: [0, 0, 1]
+    material: red
+  - type: box
+    min: [4, 0, 0]
+    max: [3, 2, -2]
+    material: glass
+";
+        let (_camera, scene) =
+            parse_scene(&format!("{}{}", SCENE, shapes), Path::new("."), 1.0).unwrap();
+        let bounds = scene.bounding_box(0.0, 0.0).unwrap();
+        assert!((bounds.minimum.y + 3.00005).abs() < 1e-9);
+        assert_eq!(bounds.maximum.x, 4.0);
+
+        let source = format!("{}{}", SCENE, shapes.replace("    u: [1, 0, 0]\n", ""));
+        assert_eq!(error_message(&source), "objects[2]: missing u");
+    }
+
+    #[test]
+    fn test_motion() {
+        let source = SCENE
+            .replace(
+                "vertical_fov: 40",
+                "vertical_fov: 40\n  time0: 0\n  time1: 0.5",
+            )
+            .replace(
+                "  - type: sphere\n    center: [2.5, 0, 0]",
+                "  - type: moving_sphere\n    center0: [2.5, 0, 0]\n    center1: [2.5, 2, 0]",
+            );
+        let (camera, scene) = parse_scene(&source, Path::new("."), 1.0).unwrap();
+        assert_eq!((camera.time0, camera.time1), (0.0, 0.5));
+        let bounds = scene.bounding_box(0.0, 0.0).unwrap();
+        assert_eq!(bounds.maximum, Vec3::new(3.0, 2.5, 1.0));
+
+        let source = SCENE.replace(
+            "vertical_fov: 40",
+            "vertical_fov: 40\n  time0: 1\n  time1: 0",
+        );
+        assert_eq!(
+            error_message(&source),
+            "camera.time1: the shutter can't close before it opens"
+        );
+    }
+
+    #[test]
+    fn test_background() {
+        let up = Ray::new(Vec3::origin(), Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let source = format!(
+            "{}background:\n  type: gradient\n  bottom: [1, 0, 0]\n  top: [0, 0, 1]\n",
+            SCENE
+        );
+        let (_camera, scene) = parse_scene(&source, Path::new("."), 1.0).unwrap();
+        assert_eq!(scene.background.color(&up), Color::new(0.0, 0.0, 1.0));
+
+        let source = format!("{}background: [0.1, 0.2, 0.3]\n", SCENE);
+        let (_camera, scene) = parse_scene(&source, Path::new("."), 1.0).unwrap();
+        assert_eq!(scene.background.color(&up), Color::new(0.1, 0.2, 0.3));
+
+        let source = format!("{}background:\n  type: starfield\n", SCENE);
+        assert_eq!(
+            error_message(&source),
+            "background.type: unknown background type \"starfield\""
+        );
+    }
+
+    #[test]
+    fn test_textures() {
+        let source = SCENE.replace(
+            "    albedo: [0.8, 0.1, 0.1]",
+            "    albedo:\n      type: checker\n      size: 0.5\n      even: [1, 1, 1]\n      odd:\n        type: checker\n        even: [0, 0, 0]\n        odd: [0.5, 0.5, 0.5]",
+        );
+        assert!(parse_scene(&source, Path::new("."), 1.0).is_ok());
+
+        let source = SCENE.replace(
+            "albedo: [0.8, 0.1, 0.1]",
+            "albedo: {type: marble, scale: 4, octaves: 3, dark: [0.2, 0.2, 0.3]}",
+        );
+        assert!(parse_scene(&source, Path::new("."), 1.0).is_ok());
+        let source = SCENE.replace(
+            "albedo: [0.8, 0.1, 0.1]",
+            "albedo: {type: wood, octaves: -1}",
+        );
+        assert_eq!(
+            error_message(&source),
+            "materials.red.albedo.octaves: expected a whole number, 0 or more"
+        );
+
+        let source = SCENE.replace("albedo: [0.8, 0.1, 0.1]", "albedo: red");
+        assert_eq!(
+            error_message(&source),
+            "materials.red.albedo: expected a color, e.g. [0.5, 0.5, 0.5], or a texture"
+        );
+        let source = SCENE.replace(
+            "albedo: [0.8, 0.1, 0.1]",
+            "albedo: {type: image, file: missing.png}",
+        );
+        assert!(error_message(&source).starts_with("materials.red.albedo.file: ./missing.png: "));
+    }
+
+    #[test]
+    fn test_instance() {
+        let instance = "
+  - type: instance
+    transform:
+      scale: [1, 3, 1]
+      translate: [0, 0, -4]
+    object:
+      type: box
+      min: [-1, -1, -1]
+      max: [1, 1, 1]
+      material: red
+";
+        let (_camera, scene) =
+            parse_scene(&format!("{}{}", SCENE, instance), Path::new("."), 1.0).unwrap();
+        let bounds = scene.bounding_box(0.0, 0.0).unwrap();
+        assert!((bounds.minimum - Vec3::new(-1.0, -3.0, -5.0)).len() < 1e-3);
+
+        let matrix = "[[1, 0, 0, 0], [0, 3, 0, 0], [0, 0, 1, -4]]";
+        let source = format!(
+            "{}{}",
+            SCENE,
+            instance.replace(
+                "\n      scale: [1, 3, 1]\n      translate: [0, 0, -4]",
+                &format!(" {}", matrix)
+            )
+        );
+        let (_camera, scene) = parse_scene(&source, Path::new("."), 1.0).unwrap();
+        let matrix_bounds = scene.bounding_box(0.0, 0.0).unwrap();
+        assert_eq!(matrix_bounds.minimum, bounds.minimum);
+        assert_eq!(matrix_bounds.maximum, bounds.maximum);
+
+        let source = format!("{}{}", SCENE, instance.replace("[1, 3, 1]", "[1, 0, 1]"));
+        assert_eq!(
+            error_message(&source),
+            "objects[2].transform: flattens everything, so it can't be undone"
+        );
+        let source = format!("{}{}", SCENE, instance.replace("min:", "minimum:"));
+        assert_eq!(error_message(&source), "objects[2].object: missing min");
+    }
+
+    #[test]
+    fn test_media() {
+        let smoke = "
+  - type: constant_medium
+    density: 0.5
+    material: smoke
+    boundary:
+      type: sphere
+      center: [0, 0, -3]
+      radius: 1
+      material: smoke
+";
+        let source = format!("{}{}", SCENE, smoke).replace(
+            "materials:\n",
+            "materials:\n  smoke:\n    type: isotropic\n    albedo: [0.2, 0.2, 0.2]\n",
+        );
+        let (_camera, scene) = parse_scene(&source, Path::new("."), 1.0).unwrap();
+        assert!(scene.fog.is_none());
+        let bounds = scene.bounding_box(0.0, 0.0).unwrap();
+        assert_eq!(bounds.minimum.z, -4.0);
+
+        let foggy = format!("{}fog:\n  density: 0.1\n", source);
+        let (_camera, scene) = parse_scene(&foggy, Path::new("."), 1.0).unwrap();
+        assert!(scene.fog.is_some());
+
+        let source = source.replace("density: 0.5", "density: 0");
+        assert_eq!(
+            error_message(&source),
+            "objects[2].density: expected a number above 0"
+        );
+    }
+
+    #[test]
+    fn test_voxel_volume() {
+        // Voxel grids are found relative to the scene file
+        let (_camera, scene) = load_scene("scenes/cloud.yml", 1.0).unwrap();
+        assert!(scene.bounding_box(0.0, 0.0).unwrap().maximum.y > 3.0);
+    }
+
+    #[test]
+    fn test_obj() {
+        // Models are found relative to the scene file
+        let (_camera, scene) = load_scene("scenes/gem.yml", 1.0).unwrap();
+        let bounds = scene.bounding_box(0.0, 0.0).unwrap();
+        assert!(bounds.maximum.x > 2.0);
+
+        let source = SCENE.replace(
+            "objects:\n",
+            "objects:\n  - type: obj\n    file: scenes/models/missing.obj\n",
+        );
+        let message = error_message(&source);
+        assert!(
+            message.starts_with("objects[0].file: ./scenes/models/missing.obj: "),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_shared_models() {
+        // Copies of an OBJ model, and instances of a named model, share one set of triangles
+        let source = SCENE
+            .replace(
+                "objects:\n",
+                "models:\n  gem:\n    type: obj\n    file: models/gem.obj\n    material: red\nobjects:\n",
+            )
+            .to_string()
+            + "
+  - {type: instance, object: gem, transform: {translate: [-3, 0, 0]}}
+  - {type: instance, object: gem, transform: {translate: [3, 0, 0]}}
+  - {type: obj, file: models/gem.obj, material: red, transform: {rotate: [0, 45, 0]}}
+  - {type: obj, file: models/gem.obj, material: red, transform: {scale: 2}}
+";
+        let (camera, scene) = parse_scene(&source, Path::new("scenes"), 1.0).unwrap();
+        assert_eq!(scene.object_list.objects.len(), 6);
+        let dumped = dump_scene(&camera, &scene).unwrap();
+        let docs = YamlLoader::load_from_str(&dumped).unwrap();
+        assert_eq!(docs[0]["models"].as_hash().unwrap().len(), 1);
+
+        // Dumps keep the models shared when they're loaded again
+        let (_camera, loaded) = parse_scene(&dumped, Path::new("."), 1.0).unwrap();
+        assert_eq!(dump_scene(&camera, &loaded).unwrap(), dumped);
+
+        let source = format!(
+            "{}  - {{type: instance, object: gem, transform: {{scale: 2}}}}\n",
+            SCENE
+        );
+        assert_eq!(
+            error_message(&source),
+            "objects[2].object: unknown model \"gem\""
+        );
+
+        // An instance of an instance writes both out, the inner one first
+        let source = SCENE.to_string()
+            + "
+  - type: instance
+    transform: {translate: [0, 2, 0]}
+    object:
+      type: instance
+      transform: {scale: 0.5}
+      object: {type: sphere, center: [0, 0, 0], radius: 1, material: red}
+";
+        let (camera, scene) = parse_scene(&source, Path::new("."), 1.0).unwrap();
+        let dumped = dump_scene(&camera, &scene).unwrap();
+        let docs = YamlLoader::load_from_str(&dumped).unwrap();
+        let models = &docs[0]["models"];
+        assert_eq!(models.as_hash().unwrap().len(), 2);
+        assert_eq!(models["model0"]["type"].as_str(), Some("sphere"));
+        assert_eq!(models["model1"]["object"].as_str(), Some("model0"));
+        let (_camera, loaded) = parse_scene(&dumped, Path::new("."), 1.0).unwrap();
+        assert_eq!(dump_scene(&camera, &loaded).unwrap(), dumped);
+    }
+}
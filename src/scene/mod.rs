@@ -1,2 +1,8 @@
 mod scene;
-pub use scene::Scene;
+pub use scene::{rays_traced, Scene};
+mod loader;
+pub use loader::load_scene;
+mod writer;
+pub use writer::{
+    color, dump_scene_to_file, hash, real, string, unsupported, uv, vec3, SceneWriter,
+};
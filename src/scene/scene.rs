@@ -1,22 +1,96 @@
 use crate::aabb::AABB;
-use crate::object::{BVHNode, HitRecord, Object, ObjectList};
+use crate::background::{Background, Gradient};
+use crate::object::{find_lights, Fog, HitRecord, Object, ObjectList, BVH};
 use crate::ray::Ray;
+use crate::vec3::Vec3;
+use rand::{Rng, RngCore};
+use std::cell::Cell;
+use std::sync::Arc;
+
+thread_local! {
+    static RAYS_TRACED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// How many rays this thread has traced through any scene so far.
+pub fn rays_traced() -> usize {
+    RAYS_TRACED.with(Cell::get)
+}
 
 pub struct Scene {
-    pub objects: BVHNode,
+    pub objects: BVH,
+    pub object_list: ObjectList,
+    pub background: Box<dyn Background + Send + Sync>,
+    pub fog: Option<Fog>,
+    /// The objects that give off light, for aiming rays at directly
+    pub lights: Vec<Arc<dyn Object + Send + Sync>>,
 }
 
 impl Scene {
     pub fn new(src_objects: &mut ObjectList) -> Scene {
-        Scene {
-            objects: BVHNode::new(src_objects, 0, src_objects.objects.len(), 0.0, 0.0),
+        let mut scene = Scene {
+            objects: BVH::new(&[], 0.0, 0.0),
+            object_list: src_objects.clone(),
+            background: Box::new(Gradient::sky()),
+            fog: None,
+            lights: vec![],
+        };
+        scene.rebuild();
+        scene
+    }
+
+    /// Builds the tree over `object_list` again, along with the list of lights, after objects
+    /// have been added, removed or moved. Only the top level is rebuilt: meshes keep their own
+    /// trees, which every instance of a mesh shares, so moving instances between frames just
+    /// means replacing them with new ones placing the same mesh.
+    pub fn rebuild(&mut self) {
+        let objects = &self.object_list.objects;
+        self.objects = BVH::new(objects, 0.0, 0.0);
+        self.lights = vec![];
+        find_lights(objects, &mut self.lights);
+    }
+
+    /// Picks a direction from `origin` towards one of the lights, chosen at random.
+    pub fn sample_light(&self, origin: &Vec3, rng: &mut dyn RngCore) -> Option<Vec3> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let light = &self.lights[rng.gen_range(0..self.lights.len())];
+        light.sample_direction(origin, rng)
+    }
+
+    /// The density, per unit solid angle, with which `sample_light` picks `direction`.
+    pub fn light_pdf(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
         }
+        let total: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.pdf(origin, direction))
+            .sum();
+        total / self.lights.len() as f64
     }
 }
 
 impl Object for Scene {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        self.objects.hit(ray, t_min, t_max, rec)
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        rec: &mut HitRecord,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        RAYS_TRACED.with(|rays| rays.set(rays.get() + 1));
+        let hit = self.objects.hit(ray, t_min, t_max, rec, rng);
+        if let Some(fog) = &self.fog {
+            // Rays that miss everything still pass through the fog on their way out of it
+            let t_end = if hit { rec.t } else { t_max };
+            if fog.scatter(ray, t_min, t_end, rec, rng) {
+                return true;
+            }
+        }
+        hit
     }
 
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<AABB> {
@@ -46,3 +120,122 @@ impl Object for Scene {
         // Some(output_box)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::matrix::Matrix;
+    use crate::object::{BoxShape, Instance, MeshData, Quad, Sphere, TriangleMesh};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_rebuild_moves_instances() {
+        // A square mesh, placed twice
+        let mesh: Arc<dyn Object + Send + Sync> = Arc::new(TriangleMesh::new(
+            Arc::new(MeshData {
+                positions: vec![
+                    Vec3::new(-1.0, -1.0, 0.0),
+                    Vec3::new(1.0, -1.0, 0.0),
+                    Vec3::new(1.0, 1.0, 0.0),
+                    Vec3::new(-1.0, 1.0, 0.0),
+                ],
+                normals: vec![],
+                uvs: vec![],
+            }),
+            vec![[0, 1, 2], [0, 2, 3]],
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+        let at = |x: f64| -> Arc<dyn Object + Send + Sync> {
+            Arc::new(Instance::new(
+                mesh.clone(),
+                Matrix::translation(&Vec3::new(x, 0.0, 0.0)),
+            ))
+        };
+        let mut scene = Scene::new(&mut ObjectList::new(vec![at(-5.0), at(5.0)]));
+        assert_eq!(Arc::strong_count(&mesh), 3);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut hits = |scene: &Scene, x: f64| {
+            let mut rec = HitRecord::new(Arc::new(Lambertian::new(Color::default())));
+            let ray = Ray::new(Vec3::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+            scene.hit(&ray, 0.001, f64::INFINITY, &mut rec, &mut rng)
+        };
+        assert!(hits(&scene, 5.0) && !hits(&scene, 20.0));
+
+        // Move the second copy and rebuild only the top of the tree
+        scene.object_list.objects[1] = at(20.0);
+        scene.rebuild();
+        assert!(hits(&scene, 20.0) && !hits(&scene, 5.0) && hits(&scene, -5.0));
+        assert_eq!(Arc::strong_count(&mesh), 3);
+    }
+
+    #[test]
+    fn test_fog_scatters_rays_that_miss() {
+        let mut scene = Scene::new(&mut ObjectList::new(vec![Arc::new(Sphere::new(
+            Vec3::new(0.0, 0.0, -5.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::default())),
+        ))]));
+        scene.fog = Some(Fog::new(0.5, Color::new(1.0, 1.0, 1.0), 10.0));
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut rec = HitRecord::new(Arc::new(Lambertian::new(Color::default())));
+        // Heading away from the sphere, rays are still scattered before they leave the fog
+        let away = Ray::new(Vec3::origin(), Vec3::new(0.0, 0.0, 2.0), 0.0);
+        let scattered = (0..100)
+            .filter(|_| scene.hit(&away, 0.001, f64::INFINITY, &mut rec, &mut rng))
+            .count();
+        assert!(scattered > 95);
+        assert!(rec.t < 5.0);
+
+        // But not once they're past its edge
+        let outside = Ray::new(Vec3::new(0.0, 0.0, 11.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(!scene.hit(&outside, 0.001, f64::INFINITY, &mut rec, &mut rng));
+    }
+
+    #[test]
+    fn test_nested_lights() {
+        let light = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+        let quad: Arc<dyn Object + Send + Sync> = Arc::new(Quad::new(
+            Vec3::new(-1.0, 2.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+            light.clone(),
+        ));
+        let sphere: Arc<dyn Object + Send + Sync> =
+            Arc::new(Sphere::new(Vec3::origin(), 1.0, light.clone()));
+        let dull: Arc<dyn Object + Send + Sync> = Arc::new(Sphere::new(
+            Vec3::origin(),
+            1.0,
+            Arc::new(Lambertian::new(Color::default())),
+        ));
+        // A light in a list, the sides of a glowing box, and a list of a light and something
+        // else placed by an instance
+        let objects: Vec<Arc<dyn Object + Send + Sync>> = vec![
+            Arc::new(ObjectList::new(vec![quad, dull.clone()])),
+            Arc::new(BoxShape::new(
+                Vec3::new(3.0, 0.0, 0.0),
+                Vec3::new(4.0, 1.0, 1.0),
+                light,
+            )),
+            Arc::new(Instance::new(
+                Arc::new(ObjectList::new(vec![sphere, dull])),
+                Matrix::translation(&Vec3::new(0.0, 0.0, -5.0)),
+            )),
+        ];
+        let scene = Scene::new(&mut ObjectList::new(objects));
+        assert_eq!(scene.lights.len(), 1 + 6 + 1);
+
+        // The instanced sphere is aimed at where it's been moved to
+        let mut rng = StdRng::seed_from_u64(1);
+        let origin = Vec3::new(0.0, 0.0, -10.0);
+        let sphere = &scene.lights[7];
+        let direction = sphere.sample_direction(&origin, &mut rng).unwrap();
+        assert!(Vec3::dot(&Vec3::unit_vector(&direction), &Vec3::new(0.0, 0.0, 1.0)) > 0.9);
+        assert!(sphere.pdf(&origin, &Vec3::new(0.0, 0.0, 1.0)) > 0.0);
+        assert_eq!(sphere.pdf(&origin, &Vec3::new(0.0, 0.0, -1.0)), 0.0);
+    }
+}
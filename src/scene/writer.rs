@@ -0,0 +1,172 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::material::Material;
+use crate::object::Object;
+use crate::scene::Scene;
+use crate::vec3::Vec3;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::sync::Arc;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter};
+
+/// Collects the materials referenced by the objects of a scene, and the models placed by
+/// instances, so that each one is written once, under a generated name, and shared by
+/// everything that uses it.
+pub struct SceneWriter {
+    materials: Hash,
+    material_names: HashMap<*const (), String>,
+    models: Hash,
+    model_names: HashMap<*const (), String>,
+}
+
+impl SceneWriter {
+    pub fn new() -> SceneWriter {
+        SceneWriter {
+            materials: Hash::new(),
+            material_names: HashMap::new(),
+            models: Hash::new(),
+            model_names: HashMap::new(),
+        }
+    }
+
+    /// Registers `material` and returns the name objects should use to refer to it.
+    pub fn material(&mut self, material: &Arc<dyn Material + Send + Sync>) -> IoResult<Yaml> {
+        let key = Arc::as_ptr(material) as *const ();
+        if let Some(name) = self.material_names.get(&key) {
+            return Ok(Yaml::String(name.clone()));
+        }
+
+        let name = format!("material{}", self.material_names.len());
+        let description = material.to_yaml(self)?;
+        self.materials
+            .insert(Yaml::String(name.clone()), description);
+        self.material_names.insert(key, name.clone());
+        Ok(Yaml::String(name))
+    }
+
+    /// Registers `model` and returns the name instances should use to refer to it.
+    pub fn model(&mut self, model: &Arc<dyn Object + Send + Sync>) -> IoResult<Yaml> {
+        let key = Arc::as_ptr(model) as *const ();
+        if let Some(name) = self.model_names.get(&key) {
+            return Ok(Yaml::String(name.clone()));
+        }
+
+        // Models inside this one are registered first, so that they get their own names and come
+        // before it in the file, where the loader will find them
+        let description = model.to_yaml(self)?;
+        let name = format!("model{}", self.model_names.len());
+        self.models.insert(Yaml::String(name.clone()), description);
+        self.model_names.insert(key, name.clone());
+        Ok(Yaml::String(name))
+    }
+
+    pub fn objects(&mut self, objects: &[Arc<dyn Object + Send + Sync>]) -> IoResult<Yaml> {
+        let objects = objects
+            .iter()
+            .map(|object| object.to_yaml(self))
+            .collect::<IoResult<Vec<Yaml>>>()?;
+        Ok(Yaml::Array(objects))
+    }
+}
+
+/// Writes `camera` and `scene` out in the format read by `load_scene`.
+pub fn dump_scene(camera: &Camera, scene: &Scene) -> IoResult<String> {
+    let mut writer = SceneWriter::new();
+    let objects = writer.objects(&scene.object_list.objects)?;
+    let mut entries = vec![
+        ("camera", camera.to_yaml()),
+        ("background", scene.background.to_yaml()?),
+    ];
+    if let Some(fog) = &scene.fog {
+        entries.push(("fog", fog.to_yaml()));
+    }
+    entries.push(("materials", Yaml::Hash(writer.materials)));
+    if !writer.models.is_empty() {
+        entries.push(("models", Yaml::Hash(writer.models)));
+    }
+    entries.push(("objects", objects));
+    let doc = hash(entries);
+
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(&doc)
+        .map_err(|err| IoError::other(format!("{:?}", err)))?;
+    out.push('\n');
+    Ok(out)
+}
+
+pub fn dump_scene_to_file(camera: &Camera, scene: &Scene, filename: &str) -> IoResult<()> {
+    fs::write(filename, dump_scene(camera, scene)?)
+}
+
+pub fn unsupported(what: &str) -> IoError {
+    IoError::new(
+        IoErrorKind::InvalidInput,
+        format!("{} cannot be written to a scene file", what),
+    )
+}
+
+pub fn hash(entries: Vec<(&str, Yaml)>) -> Yaml {
+    Yaml::Hash(
+        entries
+            .into_iter()
+            .map(|(key, value)| (Yaml::String(key.to_string()), value))
+            .collect(),
+    )
+}
+
+pub fn string(value: &str) -> Yaml {
+    Yaml::String(value.to_string())
+}
+
+pub fn real(value: f64) -> Yaml {
+    // Debug formatting gives the shortest representation that parses back to the same value.
+    Yaml::Real(format!("{:?}", value))
+}
+
+pub fn vec3(v: &Vec3) -> Yaml {
+    Yaml::Array(vec![real(v.x), real(v.y), real(v.z)])
+}
+
+pub fn uv(coords: (f64, f64)) -> Yaml {
+    Yaml::Array(vec![real(coords.0), real(coords.1)])
+}
+
+pub fn color(c: &Color) -> Yaml {
+    Yaml::Array(vec![real(c.red), real(c.green), real(c.blue)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::PathTracer;
+    use crate::scene::loader::parse_scene;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::path::Path;
+
+    #[test]
+    fn test_round_trip() {
+        let camera = crate::random_scene_camera(1.5);
+        let scene = crate::random_scene(&mut StdRng::seed_from_u64(7));
+        let dumped = dump_scene(&camera, &scene).unwrap();
+        let (loaded_camera, loaded_scene) = parse_scene(&dumped, Path::new("."), 1.5).unwrap();
+
+        assert_eq!(camera.to_yaml(), loaded_camera.to_yaml());
+        assert_eq!(
+            crate::render(&camera, &scene, 60, 40, 4, &PathTracer::new(10), 7).0,
+            crate::render(
+                &loaded_camera,
+                &loaded_scene,
+                60,
+                40,
+                4,
+                &PathTracer::new(10),
+                7
+            )
+            .0
+        );
+    }
+}
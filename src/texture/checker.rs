@@ -0,0 +1,71 @@
+use crate::color::Color;
+use crate::scene::{hash, real, string};
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+use std::io::Result as IoResult;
+use std::sync::Arc;
+use yaml_rust::Yaml;
+
+/// Fills space with alternating cubes of two other textures, so that it doesn't depend on
+/// how a surface is mapped.
+pub struct Checker {
+    size: f64,
+    even: Arc<dyn Texture + Send + Sync>,
+    odd: Arc<dyn Texture + Send + Sync>,
+}
+
+impl Checker {
+    /// A checker pattern of cubes `size` units across, with one at the origin using `even`.
+    pub fn new(
+        size: f64,
+        even: Arc<dyn Texture + Send + Sync>,
+        odd: Arc<dyn Texture + Send + Sync>,
+    ) -> Checker {
+        Checker { size, even, odd }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Color {
+        let cell =
+            (p.x / self.size).floor() + (p.y / self.size).floor() + (p.z / self.size).floor();
+        if cell as i64 % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("checker")),
+            ("size", real(self.size)),
+            ("even", self.even.to_yaml()?),
+            ("odd", self.odd.to_yaml()?),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::SolidColor;
+
+    #[test]
+    fn test_checker() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let black = Color::new(0.0, 0.0, 0.0);
+        let checker = Checker::new(
+            0.5,
+            Arc::new(SolidColor::new(white)),
+            Arc::new(SolidColor::new(black)),
+        );
+        let at = |x: f64, y: f64, z: f64| checker.value(0.0, 0.0, &Vec3::new(x, y, z));
+        assert_eq!(at(0.25, 0.25, 0.25), white);
+        assert_eq!(at(0.75, 0.25, 0.25), black);
+        assert_eq!(at(0.75, 0.75, 0.25), white);
+        // Cells carry on alternating either side of zero
+        assert_eq!(at(-0.25, 0.25, 0.25), black);
+        assert_eq!(at(-0.25, -0.25, 0.25), white);
+    }
+}
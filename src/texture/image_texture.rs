@@ -0,0 +1,106 @@
+use crate::color::Color;
+use crate::image::{PNG, PPM};
+use crate::scene::{hash, string, unsupported};
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+use std::path::Path;
+use yaml_rust::Yaml;
+
+/// Maps an image onto a surface by its texture coordinates, with (0, 0) at the image's bottom
+/// left corner and (1, 1) at its top right.
+pub struct ImageTexture {
+    image_width: u32,
+    image_height: u32,
+    image_data: Vec<Color>,
+    file: Option<String>,
+}
+
+impl ImageTexture {
+    pub fn new(image_width: u32, image_height: u32, image_data: Vec<Color>) -> ImageTexture {
+        ImageTexture {
+            image_width,
+            image_height,
+            image_data,
+            file: None,
+        }
+    }
+
+    /// Loads a PNG or PPM image, depending on the file's extension.
+    pub fn load(path: &Path) -> IoResult<ImageTexture> {
+        let in_file =
+            |err: IoError| IoError::new(err.kind(), format!("{}: {}", path.display(), err));
+        let data = fs::read(path).map_err(in_file)?;
+        let mut texture = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("png") => {
+                let png = PNG::decode(&data).map_err(in_file)?;
+                ImageTexture::new(png.image_width, png.image_height, png.image_data)
+            }
+            Some("ppm") => {
+                let ppm = PPM::decode(&data).map_err(in_file)?;
+                ImageTexture::new(ppm.image_width, ppm.image_height, ppm.image_data)
+            }
+            _ => {
+                return Err(in_file(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "expected a .png or .ppm image",
+                )))
+            }
+        };
+        if texture.image_data.is_empty() {
+            return Err(in_file(IoError::new(
+                IoErrorKind::InvalidData,
+                "image is empty",
+            )));
+        }
+        let path = fs::canonicalize(path).map_err(in_file)?;
+        texture.file = Some(path.to_string_lossy().to_string());
+        Ok(texture)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Vec3) -> Color {
+        let u = u.clamp(0.0, 1.0);
+        // Image rows run from the top down
+        let v = 1.0 - v.clamp(0.0, 1.0);
+        let x = ((u * self.image_width as f64) as u32).min(self.image_width - 1);
+        let y = ((v * self.image_height as f64) as u32).min(self.image_height - 1);
+        self.image_data[(y * self.image_width + x) as usize]
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        let file = self
+            .file
+            .as_ref()
+            .ok_or_else(|| unsupported("image texture not loaded from a file"))?;
+        Ok(hash(vec![
+            ("type", string("image")),
+            ("file", string(file)),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value() {
+        let (red, green, blue, white) = (
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 1.0),
+        );
+        let texture = ImageTexture::new(2, 2, vec![red, green, blue, white]);
+        let at = |u: f64, v: f64| texture.value(u, v, &Vec3::origin());
+        assert_eq!(at(0.25, 0.75), red);
+        assert_eq!(at(0.75, 0.75), green);
+        assert_eq!(at(0.25, 0.25), blue);
+        assert_eq!(at(1.0, 0.0), white);
+        // Out of range coordinates are clamped to the edges
+        assert_eq!(at(-3.0, 7.0), red);
+    }
+}
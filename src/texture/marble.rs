@@ -0,0 +1,62 @@
+use crate::color::Color;
+use crate::scene::{color, hash, real, string};
+use crate::texture::{Perlin, Texture};
+use crate::vec3::Vec3;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+/// Veins running across the z axis, made wavy by turbulence.
+pub struct Marble {
+    perlin: Perlin,
+    seed: u64,
+    scale: f64,
+    octaves: u32,
+    turbulence: f64,
+    dark: Color,
+    light: Color,
+}
+
+impl Marble {
+    /// Veins about `2π / scale` units apart, displaced by `turbulence` times the noise summed
+    /// over `octaves`.
+    pub fn new(
+        seed: u64,
+        scale: f64,
+        octaves: u32,
+        turbulence: f64,
+        dark: Color,
+        light: Color,
+    ) -> Marble {
+        Marble {
+            perlin: Perlin::new(&mut StdRng::seed_from_u64(seed)),
+            seed,
+            scale,
+            octaves,
+            turbulence,
+            dark,
+            light,
+        }
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        let phase = self.scale * p.z + self.turbulence * self.perlin.turbulence(p, self.octaves);
+        let t = 0.5 * (1.0 + phase.sin());
+        (1.0 - t) * &self.dark + t * &self.light
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("marble")),
+            ("seed", Yaml::Integer(self.seed as i64)),
+            ("scale", real(self.scale)),
+            ("octaves", Yaml::Integer(self.octaves as i64)),
+            ("turbulence", real(self.turbulence)),
+            ("dark", color(&self.dark)),
+            ("light", color(&self.light)),
+        ]))
+    }
+}
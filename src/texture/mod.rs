@@ -0,0 +1,16 @@
+mod texture;
+pub use texture::Texture;
+mod solid_color;
+pub use solid_color::SolidColor;
+mod checker;
+pub use checker::Checker;
+mod image_texture;
+pub use image_texture::ImageTexture;
+mod perlin;
+pub use perlin::Perlin;
+mod noise;
+pub use noise::Noise;
+mod marble;
+pub use marble::Marble;
+mod wood;
+pub use wood::Wood;
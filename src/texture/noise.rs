@@ -0,0 +1,51 @@
+use crate::color::Color;
+use crate::scene::{color, hash, real, string};
+use crate::texture::{Perlin, Texture};
+use crate::vec3::Vec3;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+/// Cloudy fractal noise, blending between two colors.
+pub struct Noise {
+    perlin: Perlin,
+    seed: u64,
+    scale: f64,
+    octaves: u32,
+    dark: Color,
+    light: Color,
+}
+
+impl Noise {
+    /// Noise with features about `1 / scale` units across, and `octaves` levels of finer detail.
+    pub fn new(seed: u64, scale: f64, octaves: u32, dark: Color, light: Color) -> Noise {
+        Noise {
+            perlin: Perlin::new(&mut StdRng::seed_from_u64(seed)),
+            seed,
+            scale,
+            octaves,
+            dark,
+            light,
+        }
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        let noise = self.perlin.fbm(&(self.scale * p), self.octaves);
+        let t = (0.5 * (1.0 + noise)).clamp(0.0, 1.0);
+        (1.0 - t) * &self.dark + t * &self.light
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("noise")),
+            ("seed", Yaml::Integer(self.seed as i64)),
+            ("scale", real(self.scale)),
+            ("octaves", Yaml::Integer(self.octaves as i64)),
+            ("dark", color(&self.dark)),
+            ("light", color(&self.light)),
+        ]))
+    }
+}
@@ -0,0 +1,118 @@
+use crate::vec3::Vec3;
+use rand::seq::SliceRandom;
+use rand::RngCore;
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise: smooth, random looking values in [-1, 1] that change over a scale of about
+/// one unit.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(rng: &mut dyn RngCore) -> Perlin {
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::unit_vector(&Vec3::random(rng, -1.0, 1.0)))
+            .collect();
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            perm.shuffle(rng);
+            perm
+        };
+        Perlin {
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+            gradients,
+        }
+    }
+
+    pub fn noise(&self, p: &Vec3) -> f64 {
+        let (i, j, k) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - i, p.y - j, p.z - k);
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+
+        // The gradients at the corners of the lattice cell around p
+        let mut corners = [[[Vec3::origin(); 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.gradients[index];
+                }
+            }
+        }
+
+        // Blend each corner's contribution with a Hermite curve, so the noise is smooth
+        // across cell boundaries.
+        let hermite = |t: f64| t * t * (3.0 - 2.0 * t);
+        let (uu, vv, ww) = (hermite(u), hermite(v), hermite(w));
+        let mut accum = 0.0;
+        for (di, plane) in corners.iter().enumerate() {
+            for (dj, row) in plane.iter().enumerate() {
+                for (dk, gradient) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * Vec3::dot(gradient, &weight);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Fractal Brownian motion: noise summed over `octaves` of doubling frequency and halving
+    /// amplitude, keeping its sign.
+    pub fn fbm(&self, p: &Vec3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, |noise| noise)
+    }
+
+    /// Like `fbm`, but summing the magnitude of each octave, for a more billowy look.
+    pub fn turbulence(&self, p: &Vec3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, f64::abs)
+    }
+
+    fn octaves(&self, p: &Vec3, octaves: u32, shape: impl Fn(f64) -> f64) -> f64 {
+        let mut accum = 0.0;
+        let mut p = *p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accum += weight * shape(self.noise(&p));
+            weight *= 0.5;
+            p *= 2.0;
+        }
+        accum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_noise() {
+        let perlin = Perlin::new(&mut StdRng::seed_from_u64(0));
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let p = Vec3::random(&mut rng, -100.0, 100.0);
+            let noise = perlin.noise(&p);
+            assert!((-1.0..=1.0).contains(&noise));
+            // Nearby points have nearby values
+            let nearby = perlin.noise(&(p + Vec3::new(1e-4, 0.0, 0.0)));
+            assert!((noise - nearby).abs() < 1e-3);
+        }
+        // The noise vanishes at lattice points
+        assert_eq!(perlin.noise(&Vec3::new(3.0, -2.0, 7.0)), 0.0);
+        assert!(perlin.turbulence(&Vec3::new(0.3, 0.6, 0.9), 5) >= 0.0);
+    }
+}
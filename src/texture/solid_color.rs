@@ -0,0 +1,26 @@
+use crate::color::Color;
+use crate::scene::color;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Vec3) -> Color {
+        self.color
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Ok(color(&self.color))
+    }
+}
@@ -0,0 +1,14 @@
+use crate::color::Color;
+use crate::scene::unsupported;
+use crate::vec3::Vec3;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+/// A color that varies over a surface, looked up by the hit's texture coordinates `u`, `v`
+/// or its position `p`.
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Color;
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Err(unsupported("texture"))
+    }
+}
@@ -0,0 +1,65 @@
+use crate::color::Color;
+use crate::scene::{color, hash, real, string};
+use crate::texture::{Perlin, Texture};
+use crate::vec3::Vec3;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io::Result as IoResult;
+use yaml_rust::Yaml;
+
+/// Growth rings around the y axis, warped by noise so that they aren't perfect circles.
+pub struct Wood {
+    perlin: Perlin,
+    seed: u64,
+    scale: f64,
+    octaves: u32,
+    turbulence: f64,
+    dark: Color,
+    light: Color,
+}
+
+impl Wood {
+    /// Rings `1 / scale` units apart, pushed in or out by up to about `turbulence` rings by
+    /// noise summed over `octaves`.
+    pub fn new(
+        seed: u64,
+        scale: f64,
+        octaves: u32,
+        turbulence: f64,
+        dark: Color,
+        light: Color,
+    ) -> Wood {
+        Wood {
+            perlin: Perlin::new(&mut StdRng::seed_from_u64(seed)),
+            seed,
+            scale,
+            octaves,
+            turbulence,
+            dark,
+            light,
+        }
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        let radius = (p.x * p.x + p.z * p.z).sqrt() * self.scale;
+        let rings = radius + self.turbulence * self.perlin.fbm(&(self.scale * p), self.octaves);
+        // Each ring fades from light early wood to dark late wood
+        let t = rings - rings.floor();
+        let t = t * t;
+        (1.0 - t) * &self.light + t * &self.dark
+    }
+
+    fn to_yaml(&self) -> IoResult<Yaml> {
+        Ok(hash(vec![
+            ("type", string("wood")),
+            ("seed", Yaml::Integer(self.seed as i64)),
+            ("scale", real(self.scale)),
+            ("octaves", Yaml::Integer(self.octaves as i64)),
+            ("turbulence", real(self.turbulence)),
+            ("dark", color(&self.dark)),
+            ("light", color(&self.light)),
+        ]))
+    }
+}
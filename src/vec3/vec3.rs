@@ -1,4 +1,4 @@
-use rand::Rng;
+use rand::{Rng, RngCore};
 use std::ops::Add;
 use std::ops::AddAssign;
 use std::ops::Div;
@@ -54,16 +54,16 @@ impl Vec3 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
-    pub fn random(min: f64, max: f64) -> Vec3 {
-        let x = rand::thread_rng().gen_range(min..max);
-        let y = rand::thread_rng().gen_range(min..max);
-        let z = rand::thread_rng().gen_range(min..max);
+    pub fn random(rng: &mut dyn RngCore, min: f64, max: f64) -> Vec3 {
+        let x = rng.gen_range(min..max);
+        let y = rng.gen_range(min..max);
+        let z = rng.gen_range(min..max);
         Vec3::new(x, y, z)
     }
 
-    pub fn random_in_unit_sphere() -> Vec3 {
+    pub fn random_in_unit_sphere(rng: &mut dyn RngCore) -> Vec3 {
         loop {
-            let v = Vec3::random(-1.0, 1.0);
+            let v = Vec3::random(rng, -1.0, 1.0);
             if v.len_squared() >= 1.0 {
                 continue;
             } else {
@@ -72,12 +72,12 @@ impl Vec3 {
         }
     }
 
-    pub fn random_unit_vector() -> Vec3 {
-        Vec3::unit_vector(&Vec3::random_in_unit_sphere())
+    pub fn random_unit_vector(rng: &mut dyn RngCore) -> Vec3 {
+        Vec3::unit_vector(&Vec3::random_in_unit_sphere(rng))
     }
 
-    pub fn random_in_hemisphere(normal: &Vec3) -> Vec3 {
-        let in_unit_sphere = Vec3::random_in_unit_sphere();
+    pub fn random_in_hemisphere(rng: &mut dyn RngCore, normal: &Vec3) -> Vec3 {
+        let in_unit_sphere = Vec3::random_in_unit_sphere(rng);
         if Vec3::dot(&in_unit_sphere, normal) > 0.0 {
             in_unit_sphere
         } else {
@@ -85,10 +85,10 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_disk() -> Vec3 {
+    pub fn random_in_unit_disk(rng: &mut dyn RngCore) -> Vec3 {
         loop {
-            let rand_num1 = rand::thread_rng().gen_range(-1.0..1.0);
-            let rand_num2 = rand::thread_rng().gen_range(-1.0..1.0);
+            let rand_num1 = rng.gen_range(-1.0..1.0);
+            let rand_num2 = rng.gen_range(-1.0..1.0);
             let p = Vec3::new(rand_num1, rand_num2, 0.0);
             if p.len_squared() >= 1.0 {
                 continue;
@@ -108,9 +108,9 @@ impl Vec3 {
     }
 
     pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
-        let cos_theta = Vec3::dot(&-(uv.clone()), n).min(1.0);
-        let r_out_perp = etai_over_etat * &(uv.clone() + cos_theta * n);
-        let r_out_parallel = -(((1.0 - r_out_perp.len_squared()).abs() as f64).sqrt()) * n;
+        let cos_theta = Vec3::dot(&-*uv, n).min(1.0);
+        let r_out_perp = etai_over_etat * &(*uv + cos_theta * n);
+        let r_out_parallel = -((1.0 - r_out_perp.len_squared()).abs().sqrt()) * n;
         r_out_perp + r_out_parallel
     }
 }
@@ -271,13 +271,13 @@ mod tests {
         assert_eq!(vec.len(), 1.0);
 
         let vec = Vec3::new(1.0, 1.0, 1.0);
-        assert!(nearly_equal(vec.len(), (3.0 as f64).sqrt()));
+        assert!(nearly_equal(vec.len(), 3.0_f64.sqrt()));
 
         let vec = Vec3::new(-1.0, -1.0, -1.0);
-        assert!(nearly_equal(vec.len(), (3.0 as f64).sqrt()));
+        assert!(nearly_equal(vec.len(), 3.0_f64.sqrt()));
 
         let vec = Vec3::new(10.0, 10.0, 10.0);
-        assert!(nearly_equal(vec.len(), (300.0 as f64).sqrt()));
+        assert!(nearly_equal(vec.len(), 300.0_f64.sqrt()));
     }
     #[test]
     fn test_len_squared() {